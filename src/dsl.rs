@@ -0,0 +1,107 @@
+//! Serializable descriptions of shapes.
+//!
+//! Enabled with the `serde` feature.
+//! A description is data, so it can be saved and loaded, e.g. as JSON,
+//! and then built into a function.
+
+use serde::{Deserialize, Serialize};
+
+use vecmath::traits::*;
+
+use {cbez, con, lin, off, qbez, seg1, Fn1};
+
+/// A serializable description of a `1d -> 3d` function.
+///
+/// Each variant corresponds to the function with the same name in lower case.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Curve1<T> {
+    /// A line, see `lin`.
+    Lin {
+        /// The start point.
+        a: [T; 3],
+        /// The end point.
+        b: [T; 3],
+    },
+    /// A quadratic bezier curve, see `qbez`.
+    QBez {
+        /// The start point.
+        a: [T; 3],
+        /// The control point.
+        b: [T; 3],
+        /// The end point.
+        c: [T; 3],
+    },
+    /// A cubic bezier curve, see `cbez`.
+    CBez {
+        /// The start point.
+        a: [T; 3],
+        /// The first control point.
+        b: [T; 3],
+        /// The second control point.
+        c: [T; 3],
+        /// The end point.
+        d: [T; 3],
+    },
+    /// Two concatenated curves, see `con`.
+    Con {
+        /// The weight of the first curve.
+        w: T,
+        /// The first curve.
+        a: Box<Curve1<T>>,
+        /// The second curve.
+        b: Box<Curve1<T>>,
+    },
+    /// A segment of a curve, see `seg1`.
+    Seg {
+        /// The range of input.
+        range: [T; 2],
+        /// The curve.
+        a: Box<Curve1<T>>,
+    },
+    /// A curve offset by a position, see `off`.
+    Off {
+        /// The offset.
+        pos: [T; 3],
+        /// The curve.
+        a: Box<Curve1<T>>,
+    },
+}
+
+impl<T: Float> Curve1<T> {
+    /// Builds the function described.
+    ///
+    /// The result is the same as calling the corresponding functions directly.
+    pub fn build(&self) -> Fn1<T> {
+        match *self {
+            Curve1::Lin {a, b} => lin(a, b),
+            Curve1::QBez {a, b, c} => qbez(a, b, c),
+            Curve1::CBez {a, b, c, d} => cbez(a, b, c, d),
+            Curve1::Con {w, ref a, ref b} => con(w, a.build(), b.build()),
+            Curve1::Seg {range, ref a} => seg1(range, a.build()),
+            Curve1::Off {pos, ref a} => off(pos, a.build()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip_builds_same_curve() {
+        let (p, q, r) = ([0.0, 0.0, 0.0], [1.0, 2.0, 0.0], [3.0, 0.0, 1.0]);
+        let desc = Curve1::Con {
+            w: 0.25,
+            a: Box::new(Curve1::Lin {a: p, b: q}),
+            b: Box::new(Curve1::Lin {a: q, b: r}),
+        };
+        let json = ::serde_json::to_string(&desc).unwrap();
+        let parsed: Curve1<f64> = ::serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, desc);
+        let (a, b) = (parsed.build(), con(0.25, lin(p, q), lin(q, r)));
+        for i in 0..=8 {
+            let t = i as f64 / 8.0;
+            assert_eq!(a(t), b(t));
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Easing functions for remapping normalized input.
+//!
+//! All easing functions map 0 to 0 and 1 to 1, and are monotonic in between.
+//! Use `reparam1` to apply an easing function to the input of a curve.
+
+use std::f64::consts::PI;
+
+use vecmath::traits::*;
+
+/// Starts slowly and accelerates.
+pub fn ease_in_quad<T: Float>(t: T) -> T {
+    t * t
+}
+
+/// Starts fast and decelerates.
+pub fn ease_out_quad<T: Float>(t: T) -> T {
+    let _1: T = One::one();
+    _1 - (_1 - t) * (_1 - t)
+}
+
+/// Accelerates until halfway and decelerates afterwards.
+pub fn ease_in_out_cubic<T: Float>(t: T) -> T
+    where f64: Cast<T>
+{
+    let _1: T = One::one();
+    let _2: T = 2.0.cast();
+    let _4: T = 4.0.cast();
+    let _05: T = 0.5.cast();
+    if t < _05 {_4 * t * t * t}
+    else {
+        let s = _2 - _2 * t;
+        _1 - s * s * s / _2
+    }
+}
+
+/// Accelerates until halfway and decelerates afterwards, following a sine wave.
+pub fn ease_in_out_sine<T: Float>(t: T) -> T
+    where f64: Cast<T>
+{
+    let _1: T = One::one();
+    let _2: T = 2.0.cast();
+    let pi: T = PI.cast();
+    (_1 - (pi * t).cos()) / _2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing_fixes_ends_and_is_monotonic() {
+        let fs: [fn(f64) -> f64; 4] = [ease_in_quad, ease_out_quad, ease_in_out_cubic, ease_in_out_sine];
+        for f in &fs {
+            assert!(f(0.0).abs() < 1e-12);
+            assert!((f(1.0) - 1.0).abs() < 1e-12);
+            for i in 0..100 {
+                assert!(f(i as f64 / 100.0) <= f((i + 1) as f64 / 100.0));
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Export of curved quads to mesh file formats.
+//!
+//! Each exporter samples the curved quad like `tri_mesh`
+//! and streams the result to any `std::io::Write`.
+//! Instead of taking a welded mesh, the exporters take the same `closed` hint as `tri_mesh`,
+//! such that seams of closed surfaces are shared without comparing positions.
+
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use vecmath::traits::*;
+
+use {cross3, len3, scale3, sub3, tri_mesh, Fn2};
+
+/// Writes a curved quad sampled on a grid of `nu x nv` points as Wavefront OBJ.
+///
+/// The vertices and faces are the same as from `tri_mesh`, including the seams welded by `closed`.
+/// When `uv` is `true`, texture coordinates are written from the input of the curved quad,
+/// one for each grid point, such that a closed seam still gets the texture coordinates of both sides.
+/// Points are written while sampling, so no mesh is kept in memory.
+pub fn write_obj<W: Write, T: Float + Display>(
+    w: &mut W,
+    nu: usize,
+    nv: usize,
+    closed: [bool; 2],
+    uv: bool,
+    a: &Fn2<T>
+) -> io::Result<()>
+    where f64: Cast<T>
+{
+    let (nu, nv) = (nu.max(2), nv.max(2));
+    let mu = if closed[0] {nu - 1} else {nu};
+    let mv = if closed[1] {nv - 1} else {nv};
+    let param = |i: usize, n: usize| i as f64 / (n - 1) as f64;
+    for i in 0..mu {
+        for j in 0..mv {
+            let pos = a([param(i, nu).cast(), param(j, nv).cast()]);
+            writeln!(w, "v {} {} {}", pos[0], pos[1], pos[2])?;
+        }
+    }
+    if uv {
+        for i in 0..nu {
+            for j in 0..nv {
+                writeln!(w, "vt {} {}", param(i, nu), param(j, nv))?;
+            }
+        }
+    }
+    // OBJ indices start at 1.
+    let vert = |(i, j): (usize, usize)| (i % mu) * mv + j % mv + 1;
+    let tex = |(i, j): (usize, usize)| i * nv + j + 1;
+    for i in 0..nu - 1 {
+        for j in 0..nv - 1 {
+            let (p00, p10, p01, p11) = ((i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1));
+            for &[p, q, r] in &[[p00, p10, p11], [p00, p11, p01]] {
+                if uv {writeln!(w, "f {}/{} {}/{} {}/{}", vert(p), tex(p), vert(q), tex(q), vert(r), tex(r))?}
+                else {writeln!(w, "f {} {} {}", vert(p), vert(q), vert(r))?}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a curved quad sampled on a grid of `nu x nv` points as binary STL.
+///
+/// The triangles are the same as from `tri_mesh`,
+/// including triangles with zero area at degenerate rows,
+/// so the triangle count in the header always matches the number of facets.
+/// STL does not share vertices, but with `closed` the facets along a seam
+/// use exactly the same corners on both sides.
+/// Facet normals are computed from the triangle geometry,
+/// and are zero for triangles with zero area.
+pub fn write_stl_binary<W: Write, T: Float + Cast<f32>>(
+    w: &mut W,
+    nu: usize,
+    nv: usize,
+    closed: [bool; 2],
+    a: &Fn2<T>
+) -> io::Result<()>
+    where f64: Cast<T>
+{
+    fn write_vec3<W: Write>(w: &mut W, v: [f32; 3]) -> io::Result<()> {
+        for x in &v {w.write_all(&x.to_le_bytes())?}
+        Ok(())
+    }
+
+    let (verts, tris) = tri_mesh(nu, nv, closed, a);
+    let mut header = [0u8; 80];
+    let title = b"binary STL written by piston3d-construct";
+    header[..title.len()].copy_from_slice(title);
+    w.write_all(&header)?;
+    w.write_all(&(tris.len() as u32).to_le_bytes())?;
+    for tri in &tris {
+        let vert = |i: u32| -> [f32; 3] {
+            let v = verts[i as usize];
+            [v[0].cast(), v[1].cast(), v[2].cast()]
+        };
+        let p = [vert(tri[0]), vert(tri[1]), vert(tri[2])];
+        let n = cross3(sub3(p[1], p[0]), sub3(p[2], p[0]));
+        let len = len3(n);
+        let n = if len > 0.0 {scale3(n, 1.0 / len)} else {[0.0; 3]};
+        write_vec3(w, n)?;
+        for &v in &p {write_vec3(w, v)?}
+        // Attribute byte count.
+        w.write_all(&[0, 0])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::flat;
+
+    #[test]
+    fn obj_round_trip_counts() {
+        for &(closed, verts) in &[([false, false], 5 * 3), ([true, true], 4 * 2)] {
+            for &uv in &[false, true] {
+                let mut buf = vec![];
+                write_obj(&mut buf, 5, 3, closed, uv, &flat()).unwrap();
+                let text = String::from_utf8(buf).unwrap();
+                let count = |tag: &str| text.lines().filter(|l| l.split(' ').next() == Some(tag)).count();
+                assert_eq!(count("v"), verts);
+                assert_eq!(count("vt"), if uv {5 * 3} else {0});
+                assert_eq!(count("f"), 2 * 4 * 2);
+                let faces = text.lines().filter(|l| l.starts_with("f ")).flat_map(|l| l.split(' ').skip(1));
+                for corner in faces {
+                    let i: usize = corner.split('/').next().unwrap().parse().unwrap();
+                    assert!(i >= 1 && i <= verts);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stl_header_count_matches_facets() {
+        let mut buf = vec![];
+        write_stl_binary(&mut buf, 6, 5, [false, false], &::tests::unit_sphere_shell()).unwrap();
+        let count = u32::from_le_bytes([buf[80], buf[81], buf[82], buf[83]]) as usize;
+        assert_eq!(count, 2 * 5 * 4);
+        assert_eq!(buf.len(), 84 + 50 * count);
+    }
+}
@@ -0,0 +1,51 @@
+//! Sampling of images as height fields.
+//!
+//! Enabled with the `image` feature.
+
+use std::sync::Arc;
+
+use image::GrayImage;
+
+/// Creates a function sampling a grayscale image by bilinear interpolation.
+///
+/// The input is normalized, such that `[0, 0]` is the center of the top-left pixel
+/// and `[1, 1]` is the center of the bottom-right pixel.
+/// Inputs outside this range are clamped to the edges.
+/// The output is the pixel value scaled to the range `0..1`,
+/// or zero for an empty image.
+///
+/// The image is copied, so it can be dropped afterwards.
+/// Use with `heightmap` to construct terrain from an image.
+pub fn image_field(img: &GrayImage) -> Arc<Fn([f64; 2]) -> f64 + Sync + Send> {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let pixels: Vec<f64> = img.pixels().map(|p| p.0[0] as f64 / 255.0).collect();
+    return Arc::new(move |t| {
+        if w == 0 || h == 0 {return 0.0}
+        let x = t[0].clamp(0.0, 1.0) * (w - 1) as f64;
+        let y = t[1].clamp(0.0, 1.0) * (h - 1) as f64;
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+        let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+        let at = |x: usize, y: usize| pixels[y * w + x];
+        let top = at(x0, y0) + (at(x1, y0) - at(x0, y0)) * fx;
+        let bottom = at(x0, y1) + (at(x1, y1) - at(x0, y1)) * fx;
+        top + (bottom - top) * fy
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bilinear_samples_of_tiny_image() {
+        let img = GrayImage::from_raw(2, 2, vec![0, 51, 102, 255]).unwrap();
+        let f = image_field(&img);
+        assert_eq!(f([0.0, 0.0]), 0.0);
+        assert_eq!(f([1.0, 0.0]), 0.2);
+        assert_eq!(f([0.0, 1.0]), 0.4);
+        assert_eq!(f([1.0, 1.0]), 1.0);
+        assert!((f([0.5, 0.5]) - 0.4).abs() < 1e-12);
+        assert_eq!(f([-1.0, 2.0]), f([0.0, 1.0]));
+    }
+}
@@ -58,8 +58,24 @@
 //! This library is an experiment to see how homotopy maps and higher order functional programming
 //! can be used to iterate on design.
 //! Function names are very short to provide good ergonomics.
+//!
+//! ### Features
+//!
+//! - `rayon`: Enables parallel sampling.
+//! - `noise`: Enables coherent noise functions.
+//! - `serde`: Enables serializable shape descriptions.
+//! - `piston`: Enables interleaved vertex buffers for rendering.
+//! - `image`: Enables sampling grayscale images as height fields.
 
 extern crate vecmath;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "image")]
+extern crate image;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_json;
 
 pub use vecmath::vec3_add as add3;
 pub use vecmath::vec2_add as add2;
@@ -67,6 +83,8 @@ pub use vecmath::vec3_sub as sub3;
 pub use vecmath::vec2_sub as sub2;
 pub use vecmath::vec3_len as len3;
 pub use vecmath::vec2_len as len2;
+pub use vecmath::vec3_dot as dot3;
+pub use vecmath::vec3_cross as cross3;
 pub use vecmath::vec3_scale as scale3;
 pub use vecmath::vec2_scale as scale2;
 pub use vecmath::vec2_cast as cast2;
@@ -74,6 +92,37 @@ pub use vecmath::vec3_cast as cast3;
 pub use vecmath::traits::*;
 
 use std::sync::Arc;
+use std::fmt;
+use std::error::Error;
+use std::f64::consts::{PI, TAU};
+use std::cmp::Ordering::Greater;
+
+pub use mesh::*;
+pub use export::*;
+pub use shape::*;
+#[cfg(feature = "noise")]
+pub use noise::*;
+#[cfg(feature = "serde")]
+pub use dsl::*;
+#[cfg(feature = "piston")]
+pub use vertex::*;
+#[cfg(feature = "image")]
+pub use image_field::*;
+
+mod mesh;
+mod export;
+mod shape;
+#[cfg(feature = "noise")]
+mod noise;
+#[cfg(feature = "serde")]
+mod dsl;
+#[cfg(feature = "piston")]
+mod vertex;
+#[cfg(feature = "image")]
+mod image_field;
+mod tables;
+
+pub mod ease;
 
 /// A function of type `1d -> 3d`.
 pub type Fn1<T> = Arc<Fn(T) -> [T; 3] + Sync + Send>;
@@ -81,6 +130,40 @@ pub type Fn1<T> = Arc<Fn(T) -> [T; 3] + Sync + Send>;
 pub type Fn2<T> = Arc<Fn([T; 2]) -> [T; 3] + Sync + Send>;
 /// A function of type `3d -> 3d`.
 pub type Fn3<T> = Arc<Fn([T; 3]) -> [T; 3] + Sync + Send>;
+/// A function of type `Nd -> 3d`, where `Fn1`, `Fn2` and `Fn3` are special cases.
+///
+/// This is experimental, to make functions generic over the input dimension.
+pub type FnN<T, const N: usize> = Arc<Fn([T; N]) -> [T; 3] + Sync + Send>;
+
+/// An error from validating construction parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConstructError {
+    /// The weight of a concatenation must be greater than 0 and less than 1.
+    WeightOutOfRange,
+    /// The margin must be greater than -0.5.
+    MarginOutOfRange,
+    /// The radius must be zero or positive.
+    NegativeRadius,
+    /// The number of vertices must fit in the index type of a mesh.
+    TooManyVertices,
+}
+
+impl fmt::Display for ConstructError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConstructError::WeightOutOfRange =>
+                write!(f, "Weight must be greater than 0 and less than 1"),
+            ConstructError::MarginOutOfRange =>
+                write!(f, "Margin must be greater than -0.5"),
+            ConstructError::NegativeRadius =>
+                write!(f, "Radius must be zero or positive"),
+            ConstructError::TooManyVertices =>
+                write!(f, "Vertex indices must fit in the index type"),
+        }
+    }
+}
+
+impl Error for ConstructError {}
 
 /// Returns a linear function.
 pub fn lin<T: Float>(a: [T; 3], b: [T; 3]) -> Fn1<T> {
@@ -94,6 +177,42 @@ pub fn lin2<T: Float>(a: Fn1<T>, b: Fn1<T>) -> Fn1<T> {
     })
 }
 
+/// Creates a smooth interpolation between two functions.
+///
+/// Same as `lin2`, but uses the smoothstep weight `3t² - 2t³` instead of `t`.
+/// The weight changes slowly near the ends,
+/// which avoids kinks in velocity when chaining functions.
+pub fn smooth2<T: Float>(a: Fn1<T>, b: Fn1<T>) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let _1: T = One::one();
+    let _2: T = 2.0.cast();
+    let _3: T = 3.0.cast();
+    return Arc::new(move |t| {
+        let w = t * t * (_3 - _2 * t);
+        add3(scale3(a(t), _1 - w), scale3(b(t), w))
+    })
+}
+
+/// Creates a weighted average of several functions.
+///
+/// The weights are divided by their sum, so they do not need to be normalized.
+/// A single function with a non-zero weight is returned unchanged.
+/// No functions, or weights summing to zero, gives the origin.
+pub fn linn<T: Float>(funcs: Vec<(T, Fn1<T>)>) -> Fn1<T> {
+    let _0: T = Zero::zero();
+    if funcs.len() == 1 && funcs[0].0 != _0 {return funcs[0].1.clone()}
+    return Arc::new(move |t| {
+        let mut sum = [_0; 3];
+        let mut total = _0;
+        for &(w, ref f) in &funcs {
+            sum = add3(sum, scale3(f(t), w));
+            total += w;
+        }
+        if total == _0 {[_0; 3]} else {scale3(sum, <T as One>::one() / total)}
+    })
+}
+
 /// Quadratic bezier curve.
 pub fn qbez<T: Float>(a: [T; 3], b: [T; 3], c: [T; 3]) -> Fn1<T> {
     lin2(lin(a, b), lin(b, c))
@@ -131,69 +250,127 @@ pub fn cquad<T: Float>(
 
         let w0 = _4 * (t[0] - _05) * (t[0] - _05) + smooth;
         let w1 = _4 * (t[1] - _05) * (t[1] - _05) + smooth;
-        // Normalize weights.
-        let (w0, w1) = (w0 / (w0 + w1), w1 / (w0 + w1));
 
         let a = add3(abx, scale3(sub3(cdx, abx), t[0]));
         let b = add3(acx, scale3(sub3(bdx, acx), t[1]));
+        // Check before normalizing weights to avoid division by zero.
+        if (w0 + w1) == _0 {return scale3(add3(a, b), _05)}
+
+        // Normalize weights.
+        let (w0, w1) = (w0 / (w0 + w1), w1 / (w0 + w1));
         if w0 == _1 {a}
         else if w1 == _1 {b}
-        else if (w0 + w1) == _0 {
-            scale3(add3(a, b), _05)
-        }
         else {
             add3(scale3(a, w0), scale3(b, w1))
         }
     })
 }
 
+/// Splits input at a concatenation weight.
+///
+/// Returns `true` and the normalized input of the first function,
+/// or `false` and the normalized input of the second function.
+/// A weight of 0 or less selects the second function, and a weight of 1 or more
+/// selects the first function, such that the input is never divided by zero.
+fn con_split<T: Float>(w: T, t: T) -> (bool, T) {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    if w <= _0 {(false, t)}
+    else if w >= _1 {(true, t)}
+    else if t < w {(true, t / w)}
+    else {(false, (t - w) / (_1 - w))}
+}
+
 /// Concatenates two `1d -> 3d` functions returning a new function.
 ///
 /// The input to the new function is normalized.
+/// A weight of 0 or less gives `b`, and a weight of 1 or more gives `a`.
 pub fn con<T: Float>(w: T, a: Fn1<T>, b: Fn1<T>) -> Fn1<T> {
     return Arc::new(move |t| {
-        if t < w {a(t / w)}
-        else {b((t - w) / (<T as One>::one() - w))}
+        match con_split(w, t) {
+            (true, t) => a(t),
+            (false, t) => b(t),
+        }
     })
 }
 
-/// Concatenates two `2d -> 3d` functions at x-weight.
-pub fn conx2<T: Float>(wx: T, a: Fn2<T>, b: Fn2<T>) -> Fn2<T> {
+/// Concatenates several `1d -> 3d` functions returning a new function.
+///
+/// Each function occupies a part of the input proportional to its weight.
+/// The weights are divided by their sum, so they do not need to be normalized.
+/// Functions with zero weight are skipped.
+/// No functions, or weights summing to zero, gives the origin.
+pub fn conn<T: Float>(pieces: Vec<(T, Fn1<T>)>) -> Fn1<T> {
+    let _0: T = Zero::zero();
+    let mut end = _0;
+    let mut segments = vec![];
+    for (w, f) in pieces {
+        if w <= _0 {continue}
+        let start = end;
+        end += w;
+        segments.push((start, end, f));
+    }
+    let total = end;
     return Arc::new(move |t| {
-        if t[0] < wx {a([t[0] / wx, t[1]])}
-        else {b(([(t[0] - wx) / (<T as One>::one() - wx), t[1]]))}
+        if segments.is_empty() {return [_0; 3]}
+        let x = t * total;
+        let i = segments.iter().position(|s| x < s.1).unwrap_or(segments.len() - 1);
+        let (start, end, ref f) = segments[i];
+        f((x - start) / (end - start))
     })
 }
 
+/// Concatenates two `2d -> 3d` functions at x-weight.
+///
+/// A weight of 0 or less gives `b`, and a weight of 1 or more gives `a`.
+pub fn conx2<T: Float>(wx: T, a: Fn2<T>, b: Fn2<T>) -> Fn2<T> {
+    con_axis(0, wx, a, b)
+}
+
 /// Concatenates two `2d -> 3d` functions at y-weight.
+///
+/// A weight of 0 or less gives `b`, and a weight of 1 or more gives `a`.
 pub fn cony2<T: Float>(wy: T, a: Fn2<T>, b: Fn2<T>) -> Fn2<T> {
-    return Arc::new(move |t| {
-        if t[1] < wy {a([t[0], t[1] / wy])}
-        else {b([t[0], (t[1] - wy) / (<T as One>::one() - wy)])}
-    })
+    con_axis(1, wy, a, b)
 }
 
 /// Concatenates two `3d -> 3d` functions at x-weight.
+///
+/// A weight of 0 or less gives `b`, and a weight of 1 or more gives `a`.
 pub fn conx3<T: Float>(wx: T, a: Fn3<T>, b: Fn3<T>) -> Fn3<T> {
-    return Arc::new(move |t| {
-        if t[0] < wx {a([t[0] / wx, t[1], t[2]])}
-        else {b(([(t[0] - wx) / (<T as One>::one() - wx), t[1], t[2]]))}
-    })
+    con_axis(0, wx, a, b)
 }
 
 /// Concates two `3d -> 3d` functions at y-weight.
+///
+/// A weight of 0 or less gives `b`, and a weight of 1 or more gives `a`.
 pub fn cony3<T: Float>(wy: T, a: Fn3<T>, b: Fn3<T>) -> Fn3<T> {
-    return Arc::new(move |t| {
-        if t[1] < wy {a([t[0], t[1] / wy, t[2]])}
-        else {b([t[0], (t[1] - wy) / (<T as One>::one() - wy), t[2]])}
-    })
+    con_axis(1, wy, a, b)
 }
 
 /// Concates two `3d -> 3d` functions at z-weight.
+///
+/// A weight of 0 or less gives `b`, and a weight of 1 or more gives `a`.
 pub fn conz3<T: Float>(wz: T, a: Fn3<T>, b: Fn3<T>) -> Fn3<T> {
+    con_axis(2, wz, a, b)
+}
+
+/// Concatenates two `Nd -> 3d` functions along an input axis.
+///
+/// A weight of 0 or less gives `b`, and a weight of 1 or more gives `a`.
+/// For example, `con_axis(0, w, a, b)` is the same as `conx2(w, a, b)` for curved quads.
+/// Panics if the axis is not less than `N`.
+pub fn con_axis<T: Float, const N: usize>(
+    axis: usize,
+    w: T,
+    a: FnN<T, N>,
+    b: FnN<T, N>
+) -> FnN<T, N> {
+    assert!(axis < N, "con_axis: axis {} out of range for {} dimensions", axis, N);
     return Arc::new(move |t| {
-        if t[2] < wz {a([t[0], t[1], t[2] / wz])}
-        else {b([t[0], t[1], (t[2] - wz) / (<T as One>::one() - wz)])}
+        let (first, x) = con_split(w, t[axis]);
+        let s: [T; N] = std::array::from_fn(|i| if i == axis {x} else {t[i]});
+        if first {a(s)} else {b(s)}
     })
 }
 
@@ -236,6 +413,28 @@ pub fn mz<T: 'static, U: Float>(
     })
 }
 
+/// Mirror shape around a plane through a point.
+///
+/// The normal of the plane is normalized, and a zero normal leaves the shape unchanged.
+/// A plane at `[x, 0, 0]` with normal `[1, 0, 0]` is the same as `mx`.
+pub fn mirror_plane<T: 'static, U: Float>(
+    point: [U; 3],
+    normal: [U; 3],
+    a: Arc<Fn(T) -> [U; 3] + Sync + Send>
+) -> Arc<Fn(T) -> [U; 3] + Sync + Send>
+    where f64: Cast<U>
+{
+    let n = match normalize3(normal) {
+        None => return a,
+        Some(n) => n,
+    };
+    let _2: U = 2.0.cast();
+    return Arc::new(move |t| {
+        let pos = a(t);
+        sub3(pos, scale3(n, _2 * dot3(sub3(pos, point), n)))
+    })
+}
+
 /// Bake mirror `2d -> 3d` around yz-plane at x coordinate.
 pub fn mirx2<T: Float>(x: T, a: Fn2<T>) -> Fn2<T>
     where f64: Cast<T>
@@ -284,6 +483,163 @@ pub fn off<T: 'static, U: Float>(
     return Arc::new(move |t| add3(a(t), pos))
 }
 
+/// A shape with any input type, like `Fn1`, `Fn2` and `Fn3`.
+type FnT<T, U> = Arc<Fn(T) -> [U; 3] + Sync + Send>;
+
+/// Creates a reusable transform that offsets shapes by a position.
+///
+/// This is the same as `off`, but curried, such that it can be mapped over many shapes.
+pub fn translate<T: 'static, U: Float>(pos: [U; 3]) -> impl Fn(FnT<T, U>) -> FnT<T, U> {
+    move |a| off(pos, a)
+}
+
+/// Rotates shape around an axis through the origin.
+///
+/// The angle is in radians, rotating counter-clockwise when the axis points toward the viewer.
+/// The axis is normalized, and a zero axis leaves the shape unchanged.
+pub fn rot<T: 'static, U: Float>(
+    axis: [U; 3],
+    angle: U,
+    a: Arc<Fn(T) -> [U; 3] + Sync + Send>
+) -> Arc<Fn(T) -> [U; 3] + Sync + Send> {
+    let k = match normalize3(axis) {
+        None => return a,
+        Some(k) => k,
+    };
+    let (sin, cos) = (angle.sin(), angle.cos());
+    // Rodrigues' rotation formula.
+    return Arc::new(move |t| {
+        let v = a(t);
+        add3(
+            add3(scale3(v, cos), scale3(cross3(k, v), sin)),
+            scale3(k, dot3(k, v) * (<U as One>::one() - cos))
+        )
+    })
+}
+
+/// Scales shape around a pivot, with a factor per axis.
+///
+/// A factor of 1 leaves the axis unchanged, and a negative factor mirrors it,
+/// e.g. a factor of `[-1, 1, 1]` is the same as `mx` at the x coordinate of the pivot.
+pub fn scl<T: 'static, U: Float>(
+    pivot: [U; 3],
+    factor: [U; 3],
+    a: Arc<Fn(T) -> [U; 3] + Sync + Send>
+) -> Arc<Fn(T) -> [U; 3] + Sync + Send> {
+    let _1: U = One::one();
+    // Same as `(pos - pivot) * factor + pivot`, but exact when factor is 1.
+    return Arc::new(move |t| {
+        let pos = a(t);
+        [
+            pos[0] * factor[0] + pivot[0] * (_1 - factor[0]),
+            pos[1] * factor[1] + pivot[1] * (_1 - factor[1]),
+            pos[2] * factor[2] + pivot[2] * (_1 - factor[2]),
+        ]
+    })
+}
+
+/// Transforms shape by a matrix in homogeneous coordinates.
+///
+/// The matrix is row major, such that the translation is stored in the last column.
+/// Column major matrices, e.g. `vecmath::col_mat4`, must be transposed first.
+/// Points are extended with `w = 1`, and divided by the resulting `w` unless the
+/// bottom row is `[0, 0, 0, 1]`.
+pub fn affine<T: 'static, U: Float>(
+    m: [[U; 4]; 4],
+    a: Arc<Fn(T) -> [U; 3] + Sync + Send>
+) -> Arc<Fn(T) -> [U; 3] + Sync + Send> {
+    let _0: U = Zero::zero();
+    let _1: U = One::one();
+    let projective = m[3] != [_0, _0, _0, _1];
+    return Arc::new(move |t| {
+        let pos = a(t);
+        let row = |r: [U; 4]| r[0] * pos[0] + r[1] * pos[1] + r[2] * pos[2] + r[3];
+        let res = [row(m[0]), row(m[1]), row(m[2])];
+        if projective {scale3(res, _1 / row(m[3]))} else {res}
+    })
+}
+
+/// Transforms shape by an arbitrary function of the output point.
+///
+/// All transforms of the output can be written this way,
+/// e.g. `off(pos, a)` is the same as `map_out(Arc::new(move |p| add3(p, pos)), a)`.
+pub fn map_out<T: 'static, U: 'static, V: 'static>(
+    f: Arc<Fn([U; 3]) -> [V; 3] + Sync + Send>,
+    a: Arc<Fn(T) -> [U; 3] + Sync + Send>
+) -> Arc<Fn(T) -> [V; 3] + Sync + Send> {
+    return Arc::new(move |t| f(a(t)))
+}
+
+/// Twists shape around the z axis.
+///
+/// Each point is rotated counter-clockwise by an angle in radians
+/// proportional to its z coordinate, such that points at `z = 0` are unchanged.
+pub fn twist<T: 'static, U: Float>(
+    angle_per_unit: U,
+    a: Arc<Fn(T) -> [U; 3] + Sync + Send>
+) -> Arc<Fn(T) -> [U; 3] + Sync + Send> {
+    return Arc::new(move |t| {
+        let pos = a(t);
+        let angle = pos[2] * angle_per_unit;
+        let (sin, cos) = (angle.sin(), angle.cos());
+        [pos[0] * cos - pos[1] * sin, pos[0] * sin + pos[1] * cos, pos[2]]
+    })
+}
+
+/// Bends shape by wrapping the x axis around a circular arc in the xy plane.
+///
+/// The arc starts at the origin in the x direction and curves toward
+/// the center at `[0, radius, 0]`, or away from it when the radius is negative.
+/// Lengths along the x axis are preserved, and points at `x = 0` are unchanged.
+/// An infinite radius leaves the shape unchanged.
+pub fn bend<T: 'static, U: Float>(
+    radius: U,
+    a: Arc<Fn(T) -> [U; 3] + Sync + Send>
+) -> Arc<Fn(T) -> [U; 3] + Sync + Send>
+    where f64: Cast<U>
+{
+    let _0: U = Zero::zero();
+    let _1: U = One::one();
+    let _2: U = 2.0.cast();
+    let inf = _1 / _0;
+    // Comparisons with NaN are false, so NaN counts as not finite.
+    let finite = |x: U| x > -inf && x < inf;
+    if !finite(radius) {return a}
+    return Arc::new(move |t| {
+        let pos = a(t);
+        let angle = pos[0] / radius;
+        let (sin, cos) = (angle.sin(), angle.cos());
+        // Uses `1 - cos = 2 sin^2(angle / 2)` to keep precision for large radii.
+        let half = (angle / _2).sin();
+        [
+            (radius - pos[1]) * sin,
+            radius * _2 * half * half + pos[1] * cos,
+            pos[2]
+        ]
+    })
+}
+
+/// Tapers shape by scaling x and y with a factor depending on z.
+///
+/// The factor is `start` at `z = range[0]` and `end` at `z = range[1]`,
+/// interpolated linearly and extrapolated outside the range.
+/// When the range is empty, the factor is `start`.
+pub fn taper<T: 'static, U: Float>(
+    start: U,
+    end: U,
+    range: [U; 2],
+    a: Arc<Fn(T) -> [U; 3] + Sync + Send>
+) -> Arc<Fn(T) -> [U; 3] + Sync + Send> {
+    let _0: U = Zero::zero();
+    let size = range[1] - range[0];
+    return Arc::new(move |t| {
+        let pos = a(t);
+        let s = if size == _0 {_0} else {(pos[2] - range[0]) / size};
+        let factor = start + (end - start) * s;
+        [pos[0] * factor, pos[1] * factor, pos[2]]
+    })
+}
+
 /// Gets the contour line of a curved quad.
 ///
 /// ```ignore
@@ -309,36 +665,184 @@ pub fn contour<T: Float>(a: Fn2<T>) -> Fn1<T>
     })
 }
 
+/// Gets the contour line of a curved quad, moving at constant speed.
+///
+/// Unlike `contour`, which gives each edge a quarter of the input,
+/// the input is reparameterized by arc length measured from `samples` segments.
+/// This means that longer edges get a larger part of the input.
+pub fn contour_uniform<T: Float>(a: Fn2<T>, samples: usize) -> Fn1<T>
+    where f64: Cast<T>
+{
+    reparam_by_length(samples, contour(a))
+}
+
+/// Gets the bottom edge of a curved quad, from `[0, 0]` to `[1, 0]`.
+///
+/// This is the same as the first quarter of `contour`.
+pub fn edge_bottom<T: Float>(a: Fn2<T>) -> Fn1<T> {
+    return Arc::new(move |t| a([t, Zero::zero()]))
+}
+
+/// Gets the right edge of a curved quad, from `[1, 0]` to `[1, 1]`.
+///
+/// This is the same as the second quarter of `contour`.
+pub fn edge_right<T: Float>(a: Fn2<T>) -> Fn1<T> {
+    return Arc::new(move |t| a([One::one(), t]))
+}
+
+/// Gets the top edge of a curved quad, from `[1, 1]` to `[0, 1]`.
+///
+/// This is the same as the third quarter of `contour`.
+pub fn edge_top<T: Float>(a: Fn2<T>) -> Fn1<T> {
+    return Arc::new(move |t| a([<T as One>::one() - t, One::one()]))
+}
+
+/// Gets the left edge of a curved quad, from `[0, 1]` to `[0, 0]`.
+///
+/// This is the same as the last quarter of `contour`.
+pub fn edge_left<T: Float>(a: Fn2<T>) -> Fn1<T> {
+    return Arc::new(move |t| a([Zero::zero(), <T as One>::one() - t]))
+}
+
+/// Gets the corners of a curved quad.
+///
+/// The corners are at `[0, 0]`, `[1, 0]`, `[1, 1]` and `[0, 1]`,
+/// in the same order as they are visited by `contour`.
+pub fn corners2<T: Float>(a: &Fn2<T>) -> [[T; 3]; 4] {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    [a([_0, _0]), a([_1, _0]), a([_1, _1]), a([_0, _1])]
+}
+
+/// Gets the corners of a volume map.
+///
+/// Corner `i` is at `[i & 1, (i >> 1) & 1, (i >> 2) & 1]`,
+/// such that the first input argument changes fastest:
+///
+/// ```ignore
+/// 0: [0, 0, 0]    4: [0, 0, 1]
+/// 1: [1, 0, 0]    5: [1, 0, 1]
+/// 2: [0, 1, 0]    6: [0, 1, 1]
+/// 3: [1, 1, 0]    7: [1, 1, 1]
+/// ```
+pub fn corners3<T: Float>(a: &Fn3<T>) -> [[T; 3]; 8] {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let mut res = [[_0; 3]; 8];
+    for (i, corner) in res.iter_mut().enumerate() {
+        let bit = |k: usize| if (i >> k) & 1 == 1 {_1} else {_0};
+        *corner = a([bit(0), bit(1), bit(2)]);
+    }
+    res
+}
+
+/// Gets the six boundary faces of a volume map.
+///
+/// The faces are at `x = 0`, `x = 1`, `y = 0`, `y = 1`, `z = 0` and `z = 1` in that order.
+/// The inputs of each face are ordered such that `normal2` points out of the cube of inputs,
+/// which is out of the solid when the volume map preserves orientation,
+/// i.e. when its Jacobian determinant is positive.
+/// Faces share their edges exactly, since they evaluate the volume map at the same inputs.
+pub fn faces3<T: Float>(a: Fn3<T>) -> [Fn2<T>; 6] {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let (a0, a1, a2, a3, a4) = (a.clone(), a.clone(), a.clone(), a.clone(), a.clone());
+    [
+        Arc::new(move |t| a0([_0, t[1], t[0]])),
+        Arc::new(move |t| a1([_1, t[0], t[1]])),
+        Arc::new(move |t| a2([t[0], _0, t[1]])),
+        Arc::new(move |t| a3([t[1], _1, t[0]])),
+        Arc::new(move |t| a4([t[1], t[0], _0])),
+        Arc::new(move |t| a([t[0], t[1], _1])),
+    ]
+}
+
+/// Clamps a margin and computes the input scale for it.
+///
+/// The margin is clamped to slightly greater than -0.5, such that the denominator
+/// of the scale `1 / (1 + 2 * m)` is a small positive number instead of zero.
+/// Returns the clamped margin and the scale.
+fn margin_scale<T: Float>(m: T) -> (T, T)
+    where f64: Cast<T>
+{
+    let _1: T = 1.0.cast();
+    let _2: T = 2.0.cast();
+    let eps: T = 0.000001.cast();
+    let m = m.max((eps - _1) / _2);
+    (m, _1 / (_1 + _2 * m))
+}
+
 /// Adds a margin to input of a `1d -> 3d` function.
+///
+/// A positive margin uses a smaller part of the input range,
+/// while a negative margin extends the input range beyond 0 and 1.
+/// The margin should be greater than -0.5, which is where the input range becomes infinite.
+/// Margins of -0.5 or less are treated as slightly greater than -0.5.
+/// See `try_margin1` for validating the margin instead.
 pub fn margin1<T: Float>(m: T, a: Fn1<T>) -> Fn1<T>
     where f64: Cast<T>
 {
-    let _1 = 1.0.cast();
-    let _2 = 2.0.cast();
-    let s = _1 / (_1 + _2 * m);
+    let (m, s) = margin_scale(m);
     return Arc::new(move |t| a((t + m) * s))
 }
 
 /// Adds a margin to input of a `2d -> 3d` function.
+///
+/// Uses the same range of margins as `margin1`.
 pub fn margin2<T: Float>(m: T, a: Fn2<T>) -> Fn2<T>
     where f64: Cast<T>
 {
-    let _1 = 1.0.cast();
-    let _2 = 2.0.cast();
-    let s = _1 / (_1 + _2 * m);
+    let (m, s) = margin_scale(m);
     return Arc::new(move |t| a([(t[0] + m) * s, (t[1] + m) * s]))
 }
 
 /// Adds a margin to input of a `3d -> 3d` function.
+///
+/// Uses the same range of margins as `margin1`.
 pub fn margin3<T: Float>(m: T, a: Fn3<T>) -> Fn3<T>
     where f64: Cast<T>
 {
-    let _1 = 1.0.cast();
-    let _2 = 2.0.cast();
-    let s = _1 / (_1 + _2 * m);
+    let (m, s) = margin_scale(m);
     return Arc::new(move |t| a([(t[0] + m) * s, (t[1] + m) * s, (t[2] + m) * s]))
 }
 
+/// Adds a margin to input of a `1d -> 3d` function, clamping the input between 0 and 1.
+///
+/// This is intended for negative margins, which extend the input range beyond 0 and 1.
+/// Instead of evaluating the function outside its normalized range,
+/// the extended parts repeat the end points.
+pub fn margin_clamp1<T: Float>(m: T, a: Fn1<T>) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let (m, s) = margin_scale(m);
+    let (_0, _1) = (Zero::zero(), One::one());
+    return Arc::new(move |t| a(((t + m) * s).max(_0).min(_1)))
+}
+
+/// Adds a margin to input of a `2d -> 3d` function, clamping the input between 0 and 1.
+///
+/// Uses the same extension as `margin_clamp1`.
+pub fn margin_clamp2<T: Float>(m: T, a: Fn2<T>) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let (m, s) = margin_scale(m);
+    let (_0, _1) = (Zero::zero(), One::one());
+    let f = move |x: T| ((x + m) * s).max(_0).min(_1);
+    return Arc::new(move |t| a([f(t[0]), f(t[1])]))
+}
+
+/// Adds a margin to input of a `3d -> 3d` function, clamping the input between 0 and 1.
+///
+/// Uses the same extension as `margin_clamp1`.
+pub fn margin_clamp3<T: Float>(m: T, a: Fn3<T>) -> Fn3<T>
+    where f64: Cast<T>
+{
+    let (m, s) = margin_scale(m);
+    let (_0, _1) = (Zero::zero(), One::one());
+    let f = move |x: T| ((x + m) * s).max(_0).min(_1);
+    return Arc::new(move |t| a([f(t[0]), f(t[1]), f(t[2])]))
+}
+
 /// Creates a circle located at a center and with a radius.
 ///
 /// The first input argument is the angle starting at 0,
@@ -349,7 +853,7 @@ pub fn margin3<T: Float>(m: T, a: Fn3<T>) -> Fn3<T>
 pub fn circle<T: Float>(center: [T; 3], radius: T) -> Fn2<T>
     where f64: Cast<T>
 {
-    let two_pi = 6.283185307179586.cast();
+    let two_pi = TAU.cast();
     return Arc::new(move |t| {
         let angle = t[0] * two_pi;
         [
@@ -360,6 +864,70 @@ pub fn circle<T: Float>(center: [T; 3], radius: T) -> Fn2<T>
     })
 }
 
+/// Creates an ellipse located at a center and with a radius along the x and y axis.
+///
+/// The input arguments are the same as for `circle`,
+/// and equal radii give the same points as `circle`.
+pub fn ellipse<T: Float>(center: [T; 3], rx: T, ry: T) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let two_pi = TAU.cast();
+    return Arc::new(move |t| {
+        let angle = t[0] * two_pi;
+        [
+            center[0] + rx * t[1] * angle.cos(),
+            center[1] + ry * t[1] * angle.sin(),
+            center[2]
+        ]
+    })
+}
+
+/// Creates a disc located at a center and with a radius, facing along a normal.
+///
+/// The input arguments are the same as for `circle`,
+/// with the angle rotating counterclockwise when looking against the normal.
+/// A zero normal faces along the z axis.
+///
+/// Like `circle`, every angle meets at the center when the radius argument is 0,
+/// so meshing gives a fan of degenerate triangles there.
+/// Use `disc_quad` to avoid this.
+pub fn disc<T: Float>(center: [T; 3], radius: T, normal: [T; 3]) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let two_pi = TAU.cast();
+    let (u, v) = axis_basis(normal);
+    return Arc::new(move |t| {
+        let angle = t[0] * two_pi;
+        let r = radius * t[1];
+        add3(center, add3(scale3(u, r * angle.cos()), scale3(v, r * angle.sin())))
+    })
+}
+
+/// Creates a disc like `disc`, but mapping a square to the disc without a pole.
+///
+/// Uses the Fernández-Guasti squircle mapping,
+/// where the edges of the square map to the rim of the disc
+/// and the center of the square maps to the center of the disc.
+/// The first input argument moves along the first tangent and the second input argument
+/// along the second tangent, such that the normal follows the cross product of them.
+pub fn disc_quad<T: Float>(center: [T; 3], radius: T, normal: [T; 3]) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let _2: T = 2.0.cast();
+    let (u, v) = axis_basis(normal);
+    return Arc::new(move |t| {
+        let x = _2 * t[0] - _1;
+        let y = _2 * t[1] - _1;
+        let (x2, y2) = (x * x, y * y);
+        let len = (x2 + y2).sqrt();
+        if len == _0 {return center}
+        let s = radius * (x2 + y2 - x2 * y2).sqrt() / len;
+        add3(center, add3(scale3(u, x * s), scale3(v, y * s)))
+    })
+}
+
 /// Creates a sphere located at a center and with a radius.
 ///
 /// The two first arguments are angles, the third is radius.
@@ -369,44 +937,166 @@ pub fn circle<T: Float>(center: [T; 3], radius: T) -> Fn2<T>
 pub fn sphere<T: Float>(center: [T; 3], radius: T) -> Fn3<T>
     where f64: Cast<T>
 {
-    let two_pi = 6.283185307179586.cast();
+    return Arc::new(move |t| sphere_at(center, radius, t))
+}
+
+/// Computes a point on a sphere, see `sphere`.
+fn sphere_at<T: Float>(center: [T; 3], radius: T, t: [T; 3]) -> [T; 3]
+    where f64: Cast<T>
+{
+    let two_pi: T = TAU.cast();
+    let _1: T = 1.0.cast();
+    let _2: T = 2.0.cast();
+    let angle0 = t[0] * two_pi;
+    let tx = _2 * t[1] - _1;
+    let rad = radius * (_1 - tx * tx).sqrt();
+    [
+        center[0] + rad * t[2] * angle0.cos(),
+        center[1] + rad * t[2] * angle0.sin(),
+        center[2] - radius + _2 * radius * t[1],
+    ]
+}
+
+/// Creates an ellipsoid located at a center and with a radius along each axis.
+///
+/// The input arguments are the same as for `sphere`, including the poles,
+/// and equal radii give the same points as `sphere`.
+pub fn ellipsoid<T: Float>(center: [T; 3], r: [T; 3]) -> Fn3<T>
+    where f64: Cast<T>
+{
+    let two_pi = TAU.cast();
     let _1 = 1.0.cast();
     let _2 = 2.0.cast();
     return Arc::new(move |t| {
         let angle0 = t[0] * two_pi;
         let tx = _2 * t[1] - _1;
-        let rad = radius * (_1 - tx * tx).sqrt();
+        let rad = (_1 - tx * tx).sqrt();
         [
-            center[0] + rad * t[2] * angle0.cos(),
-            center[1] + rad * t[2] * angle0.sin(),
-            center[2] - radius + _2 * radius * t[1],
+            center[0] + r[0] * rad * t[2] * angle0.cos(),
+            center[1] + r[1] * rad * t[2] * angle0.sin(),
+            center[2] - r[2] + _2 * r[2] * t[1],
         ]
     })
 }
 
-/// Intersects a curved quad at x-line.
-pub fn x2<T: Float>(x: T, a: Fn2<T>) -> Fn1<T>
-    where f64: Cast<T>
-{
-    return Arc::new(move |t| a([x, t]))
-}
-
-/// Intersects a curved quad at y-line.
-pub fn y2<T: Float>(y: T, a: Fn2<T>) -> Fn1<T> {
-    return Arc::new(move |t| a([t, y]))
+/// Creates an axis-aligned box between two corners.
+///
+/// Each input argument moves linearly from `min` to `max` along its axis,
+/// such that the unit cube maps onto the box.
+pub fn boxed<T: Float>(min: [T; 3], max: [T; 3]) -> Fn3<T> {
+    return Arc::new(move |t| [
+        min[0] + (max[0] - min[0]) * t[0],
+        min[1] + (max[1] - min[1]) * t[1],
+        min[2] + (max[2] - min[2]) * t[2],
+    ])
 }
 
-/// Intersects a curved cube at x-plane.
-pub fn x3<T: Float>(x: T, a: Fn3<T>) -> Fn2<T> {
-    return Arc::new(move |t| a([x, t[0], t[1]]))
+/// Creates a flat quad spanned by two edge vectors from an origin.
+///
+/// The first input argument moves along `u`, the second input argument moves along `v`,
+/// so the normal points along the cross product of `u` and `v`.
+pub fn plane<T: Float>(origin: [T; 3], u: [T; 3], v: [T; 3]) -> Fn2<T> {
+    return Arc::new(move |t| add3(origin, add3(scale3(u, t[0]), scale3(v, t[1]))))
 }
 
-/// Intersects a curved cube at y-plane.
-pub fn y3<T: Float>(y: T, a: Fn3<T>) -> Fn2<T> {
-    return Arc::new(move |t| a([t[0], y, t[1]]))
+/// Creates a terrain surface from a height field.
+///
+/// The input arguments are scaled by `size` along the x and y axes,
+/// and the height field is sampled with the same input arguments for the z axis.
+/// A constant height field gives a flat quad like `plane`.
+pub fn heightmap<T: Float>(size: [T; 2], h: Arc<Fn([T; 2]) -> T + Sync + Send>) -> Fn2<T> {
+    return Arc::new(move |t| [t[0] * size[0], t[1] * size[1], h(t)])
 }
 
-/// Intersects a curved cube at z-plane.
+/// Creates a cylinder located at a center and with a radius and height.
+///
+/// The first input argument is the angle around the z axis.
+/// The second input argument is the radius starting at the axis and ending at the wall.
+/// The third input argument is the height starting at the bottom and ending at the top.
+///
+/// The center is located halfway between the bottom and the top.
+pub fn cylinder<T: Float>(center: [T; 3], radius: T, height: T) -> Fn3<T>
+    where f64: Cast<T>
+{
+    let two_pi = TAU.cast();
+    let _05: T = 0.5.cast();
+    return Arc::new(move |t| {
+        let angle = t[0] * two_pi;
+        [
+            center[0] + radius * t[1] * angle.cos(),
+            center[1] + radius * t[1] * angle.sin(),
+            center[2] + height * (t[2] - _05),
+        ]
+    })
+}
+
+/// Creates a cone with an apex and a base located at a center and with a radius.
+///
+/// The first input argument is the angle around the axis from the apex to the base.
+/// The second input argument is the radius starting at the axis and ending at the wall.
+/// The third input argument starts at the apex and ends at the base.
+///
+/// When the apex and the base center coincide, the cone is a flat disc in the xy-plane.
+pub fn cone<T: Float>(apex: [T; 3], base_center: [T; 3], radius: T) -> Fn3<T>
+    where f64: Cast<T>
+{
+    let two_pi = TAU.cast();
+    let (u, v) = axis_basis(sub3(base_center, apex));
+    return Arc::new(move |t| {
+        let angle = t[0] * two_pi;
+        let rad = radius * t[1] * t[2];
+        let center = add3(apex, scale3(sub3(base_center, apex), t[2]));
+        add3(center, add3(scale3(u, rad * angle.cos()), scale3(v, rad * angle.sin())))
+    })
+}
+
+/// Creates an animated sphere located at a center with a radius changing over time.
+///
+/// The first argument is the animation time, which is passed to `radius`.
+/// The second argument uses the same parameterization as `sphere`.
+pub fn sphere_anim<T: Float>(
+    center: [T; 3],
+    radius: Arc<Fn(T) -> T + Sync + Send>
+) -> Arc<Fn(T, [T; 3]) -> [T; 3] + Sync + Send>
+    where f64: Cast<T>
+{
+    return Arc::new(move |time, t| sphere_at(center, radius(time), t))
+}
+
+/// Creates the frames of an animated curve.
+///
+/// Calls `make` with the animation time for each frame,
+/// where the time is evenly spaced from 0 at the first frame to 1 at the last frame.
+/// A single frame uses time 0.
+pub fn animate1<T: Float>(frames: usize, make: Arc<Fn(T) -> Fn1<T> + Sync + Send>) -> Vec<Fn1<T>>
+    where f64: Cast<T>
+{
+    (0..frames).map(|i| make(grid_param(i, frames))).collect()
+}
+
+/// Intersects a curved quad at x-line.
+pub fn x2<T: Float>(x: T, a: Fn2<T>) -> Fn1<T>
+    where f64: Cast<T>
+{
+    return Arc::new(move |t| a([x, t]))
+}
+
+/// Intersects a curved quad at y-line.
+pub fn y2<T: Float>(y: T, a: Fn2<T>) -> Fn1<T> {
+    return Arc::new(move |t| a([t, y]))
+}
+
+/// Intersects a curved cube at x-plane.
+pub fn x3<T: Float>(x: T, a: Fn3<T>) -> Fn2<T> {
+    return Arc::new(move |t| a([x, t[0], t[1]]))
+}
+
+/// Intersects a curved cube at y-plane.
+pub fn y3<T: Float>(y: T, a: Fn3<T>) -> Fn2<T> {
+    return Arc::new(move |t| a([t[0], y, t[1]]))
+}
+
+/// Intersects a curved cube at z-plane.
 pub fn z3<T: Float>(z: T, a: Fn3<T>) -> Fn2<T> {
     return Arc::new(move |t| a([t[0], t[1], z]))
 }
@@ -427,3 +1117,2666 @@ pub fn ext2<T: Float>(a: Fn1<T>, b: Fn2<T>) -> Fn3<T> {
 pub fn seg1<T: Float>(range: [T; 2], a: Fn1<T>) -> Fn1<T> {
     return Arc::new(move |t| a(range[0] + (range[1] - range[0]) * t))
 }
+
+/// Uses a range per input argument to pick a segment of a curved quad.
+///
+/// A reversed range, e.g. `[1.0, 0.0]`, flips the direction of that input argument.
+pub fn seg2<T: Float>(u_range: [T; 2], v_range: [T; 2], a: Fn2<T>) -> Fn2<T> {
+    return Arc::new(move |t| a([
+        u_range[0] + (u_range[1] - u_range[0]) * t[0],
+        v_range[0] + (v_range[1] - v_range[0]) * t[1],
+    ]))
+}
+
+/// Uses a range per input argument to pick a segment of a curved cube.
+///
+/// A reversed range, e.g. `[1.0, 0.0]`, flips the direction of that input argument.
+pub fn seg3<T: Float>(
+    x_range: [T; 2],
+    y_range: [T; 2],
+    z_range: [T; 2],
+    a: Fn3<T>
+) -> Fn3<T> {
+    return Arc::new(move |t| a([
+        x_range[0] + (x_range[1] - x_range[0]) * t[0],
+        y_range[0] + (y_range[1] - y_range[0]) * t[1],
+        z_range[0] + (z_range[1] - z_range[0]) * t[2],
+    ]))
+}
+
+/// Reverses first input direction of a curved quad.
+pub fn revu2<T: Float>(a: Fn2<T>) -> Fn2<T> {
+    seg2([One::one(), Zero::zero()], [Zero::zero(), One::one()], a)
+}
+
+/// Reverses second input direction of a curved quad.
+pub fn revv2<T: Float>(a: Fn2<T>) -> Fn2<T> {
+    seg2([Zero::zero(), One::one()], [One::one(), Zero::zero()], a)
+}
+
+/// Reverses x-input direction of a curved cube.
+pub fn revx3<T: Float>(a: Fn3<T>) -> Fn3<T> {
+    let (_0, _1) = (Zero::zero(), One::one());
+    seg3([_1, _0], [_0, _1], [_0, _1], a)
+}
+
+/// Reverses y-input direction of a curved cube.
+pub fn revy3<T: Float>(a: Fn3<T>) -> Fn3<T> {
+    let (_0, _1) = (Zero::zero(), One::one());
+    seg3([_0, _1], [_1, _0], [_0, _1], a)
+}
+
+/// Reverses z-input direction of a curved cube.
+pub fn revz3<T: Float>(a: Fn3<T>) -> Fn3<T> {
+    let (_0, _1) = (Zero::zero(), One::one());
+    seg3([_0, _1], [_0, _1], [_1, _0], a)
+}
+
+/// Swaps the input arguments of a curved quad.
+pub fn swap2<T: Float>(a: Fn2<T>) -> Fn2<T> {
+    return Arc::new(move |t| a([t[1], t[0]]))
+}
+
+/// Permutes the input arguments of a curved cube.
+///
+/// The input argument `i` of the original curved cube is taken from
+/// input argument `order[i]` of the new one.
+/// For example, `[1, 0, 2]` swaps the x- and y-input.
+///
+/// Panics if `order` is not a permutation of `[0, 1, 2]`.
+pub fn swizzle3<T: Float>(order: [usize; 3], a: Fn3<T>) -> Fn3<T> {
+    let mut sorted = order;
+    sorted.sort();
+    assert!(sorted == [0, 1, 2],
+        "swizzle3: order must be a permutation of [0, 1, 2], found {:?}", order);
+    return Arc::new(move |t| a([t[order[0]], t[order[1]], t[order[2]]]))
+}
+
+/// Transforms the input of a curve by an arbitrary function.
+///
+/// The identity function gives the same curve.
+pub fn map_in1<T: Float>(g: Arc<Fn(T) -> T + Sync + Send>, a: Fn1<T>) -> Fn1<T> {
+    return Arc::new(move |t| a(g(t)))
+}
+
+/// Transforms the input of a curved quad by an arbitrary function.
+///
+/// All transforms of the input can be written this way,
+/// e.g. `swap2(a)` is the same as `map_in2(Arc::new(|t| [t[1], t[0]]), a)`.
+/// The identity function gives the same curved quad.
+pub fn map_in2<T: Float>(g: Arc<Fn([T; 2]) -> [T; 2] + Sync + Send>, a: Fn2<T>) -> Fn2<T> {
+    return Arc::new(move |t| a(g(t)))
+}
+
+/// Transforms the input of a curved cube by an arbitrary function.
+///
+/// The identity function gives the same curved cube.
+pub fn map_in3<T: Float>(g: Arc<Fn([T; 3]) -> [T; 3] + Sync + Send>, a: Fn3<T>) -> Fn3<T> {
+    return Arc::new(move |t| a(g(t)))
+}
+
+/// Splits a normalized input into one of `count` equal segments.
+///
+/// Returns the segment index and the normalized input within the segment.
+/// Inputs outside the range from 0 to 1 extrapolate the first or last segment.
+fn split<T: Float>(t: T, count: usize) -> (usize, T)
+    where f64: Cast<T>
+{
+    let x = t * (count as f64).cast();
+    let i = last_where(count, |i| (i as f64).cast() <= x);
+    (i, x - (i as f64).cast())
+}
+
+/// Finds the last index below `n` where `f` is `true`, or 0 if there is none.
+///
+/// Uses binary search, assuming `f` is `true` up to some index and `false` after it.
+fn last_where<F: Fn(usize) -> bool>(n: usize, f: F) -> usize {
+    let (mut lo, mut hi) = (0, n - 1);
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if f(mid) {lo = mid} else {hi = mid - 1}
+    }
+    lo
+}
+
+/// Creates a curved quad from a grid of points by bilinear interpolation.
+///
+/// The outer index of the grid follows the first input argument,
+/// the inner index follows the second input argument.
+/// A grid with a single row or column is constant along that direction.
+///
+/// Panics if the grid is empty or if the rows have different lengths.
+pub fn from_grid2<T: Float>(grid: Vec<Vec<[T; 3]>>) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let m = grid.len();
+    assert!(m > 0, "from_grid2: grid must not be empty");
+    let n = grid[0].len();
+    assert!(n > 0, "from_grid2: grid rows must not be empty");
+    assert!(grid.iter().all(|row| row.len() == n),
+        "from_grid2: grid rows must have the same length");
+    let _0: T = Zero::zero();
+    return Arc::new(move |t| {
+        let (i, u) = if m == 1 {(0, _0)} else {split(t[0], m - 1)};
+        let (j, v) = if n == 1 {(0, _0)} else {split(t[1], n - 1)};
+        let i1 = (i + 1).min(m - 1);
+        let j1 = (j + 1).min(n - 1);
+        let a = add3(grid[i][j], scale3(sub3(grid[i1][j], grid[i][j]), u));
+        let b = add3(grid[i][j1], scale3(sub3(grid[i1][j1], grid[i][j1]), u));
+        add3(a, scale3(sub3(b, a), v))
+    })
+}
+
+/// Sweeps a profile along a path.
+///
+/// The first input argument moves along the path,
+/// the second input argument moves along the profile.
+/// The profile is treated as a curve in the xy-plane,
+/// which is rotated into the plane perpendicular to the path.
+/// The tangent of the path is computed with finite differences with step `eps`.
+///
+/// The frame is oriented using the z axis as up direction,
+/// falling back to the x axis where the path is parallel to the z axis.
+/// Since the up direction is fixed, straight paths do not flip the frame.
+/// For a rotation-minimizing frame, use `frame`, or `frames1` to sample
+/// many frames without walking the path from the start each time.
+pub fn sweep<T: Float>(eps: T, path: Fn1<T>, profile: Fn1<T>) -> Fn2<T> {
+    return Arc::new(move |t| sweep_at(eps, &path, profile(t[1]), t[0]))
+}
+
+/// Sweeps a profile along a path while scaling the profile.
+///
+/// Same as `sweep`, but the profile is scaled by `scale` at the current position
+/// along the path, and the tangent is computed with a step of 0.001.
+pub fn sweep_scaled<T: Float>(
+    path: Fn1<T>,
+    profile: Fn1<T>,
+    scale: Arc<Fn(T) -> T + Sync + Send>
+) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let eps: T = 0.001.cast();
+    return Arc::new(move |t| {
+        sweep_at(eps, &path, scale3(profile(t[1]), scale(t[0])), t[0])
+    })
+}
+
+/// Places a profile point in the frame of a path.
+fn sweep_at<T: Float>(eps: T, path: &Fn1<T>, p: [T; 3], t: T) -> [T; 3] {
+    let (tangent, normal, binormal) = up_frame(eps, path, t);
+    add3(path(t), add3(
+        add3(scale3(normal, p[0]), scale3(binormal, p[1])),
+        scale3(tangent, p[2])
+    ))
+}
+
+/// Normalizes a vector, returning `None` if it has zero length.
+fn normalize3<T: Float>(v: [T; 3]) -> Option<[T; 3]> {
+    let len = len3(v);
+    if len == Zero::zero() {None} else {Some(scale3(v, <T as One>::one() / len))}
+}
+
+/// Computes the finite difference of a curve, using one-sided differences at the ends.
+fn diff1<T: Float>(eps: T, a: &Fn1<T>, t: T) -> [T; 3] {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let t0 = (t - eps).max(_0);
+    let t1 = (t + eps).min(_1);
+    scale3(sub3(a(t1), a(t0)), _1 / (t1 - t0))
+}
+
+/// Computes a `(tangent, normal, binormal)` frame of a curve using an up direction.
+///
+/// Uses the z axis as up direction, or the x axis when the tangent is parallel to z.
+fn up_frame<T: Float>(eps: T, a: &Fn1<T>, t: T) -> ([T; 3], [T; 3], [T; 3]) {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let tangent = normalize3(diff1(eps, a, t)).unwrap_or([_0, _0, _1]);
+    let normal = normalize3(cross3([_0, _0, _1], tangent))
+        .unwrap_or_else(|| normalize3(cross3([_1, _0, _0], tangent)).unwrap());
+    let binormal = cross3(tangent, normal);
+    (tangent, normal, binormal)
+}
+
+/// Computes the Bernstein basis of degree `n - 1` at `t`.
+fn bernstein<T: Float>(n: usize, t: T) -> Vec<T> {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let mut b = vec![_0; n];
+    if n == 0 {return b}
+    b[0] = _1;
+    for k in 1..n {
+        for i in (1..k + 1).rev() {
+            b[i] = (_1 - t) * b[i] + t * b[i - 1];
+        }
+        b[0] = (_1 - t) * b[0];
+    }
+    b
+}
+
+/// Tensor product bezier surface from a grid of control points.
+///
+/// The outer index of the grid follows the first input argument,
+/// the inner index follows the second input argument.
+///
+/// Panics if the grid is empty or if the rows have different lengths.
+pub fn bez2<T: Float>(points: Vec<Vec<[T; 3]>>) -> Fn2<T> {
+    let m = points.len();
+    assert!(m > 0, "bez2: grid must not be empty");
+    let n = points[0].len();
+    assert!(n > 0, "bez2: grid rows must not be empty");
+    assert!(points.iter().all(|row| row.len() == n),
+        "bez2: grid rows must have the same length");
+    return Arc::new(move |t| {
+        let bu = bernstein(m, t[0]);
+        let bv = bernstein(n, t[1]);
+        let mut pos = [Zero::zero(); 3];
+        for i in 0..m {
+            for j in 0..n {
+                pos = add3(pos, scale3(points[i][j], bu[i] * bv[j]));
+            }
+        }
+        pos
+    })
+}
+
+/// Fits a tensor product bezier surface with `m x n` control points to a target.
+///
+/// The target is sampled on a `samples x samples` grid.
+/// The control points start at the target evaluated on an even grid,
+/// followed by `iters` steps of preconditioned gradient descent on the squared error.
+/// This is useful to replace an expensive construction with a cheaper approximation.
+pub fn fit_bez2<T: Float>(
+    target: &Fn2<T>,
+    m: usize,
+    n: usize,
+    samples: usize,
+    iters: usize
+) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let (m, n, samples) = (m.max(1), n.max(1), samples.max(2));
+    let norm = |i: usize, count: usize| -> T {
+        if count <= 1 {_0} else {(i as f64 / (count - 1) as f64).cast()}
+    };
+    let mut points: Vec<Vec<[T; 3]>> = (0..m)
+        .map(|i| (0..n).map(|j| target([norm(i, m), norm(j, n)])).collect())
+        .collect();
+
+    let mut params = vec![];
+    for i in 0..samples {
+        for j in 0..samples {
+            let (u, v) = (norm(i, samples), norm(j, samples));
+            params.push((bernstein(m, u), bernstein(n, v), target([u, v])));
+        }
+    }
+
+    for _ in 0..iters {
+        let mut grad = vec![vec![[_0; 3]; n]; m];
+        let mut weight = vec![vec![_0; n]; m];
+        for &(ref bu, ref bv, pos) in &params {
+            let mut fit = [_0; 3];
+            for i in 0..m {
+                for j in 0..n {
+                    fit = add3(fit, scale3(points[i][j], bu[i] * bv[j]));
+                }
+            }
+            let err = sub3(fit, pos);
+            for i in 0..m {
+                for j in 0..n {
+                    let w = bu[i] * bv[j];
+                    grad[i][j] = add3(grad[i][j], scale3(err, w));
+                    weight[i][j] += w;
+                }
+            }
+        }
+        for i in 0..m {
+            for j in 0..n {
+                if weight[i][j] > _0 {
+                    points[i][j] = sub3(points[i][j], scale3(grad[i][j], <T as One>::one() / weight[i][j]));
+                }
+            }
+        }
+    }
+    bez2(points)
+}
+
+/// Checks that a concatenation weight is greater than 0 and less than 1.
+fn check_weight<T: Float>(w: T) -> Result<(), ConstructError> {
+    if w > Zero::zero() && w < One::one() {Ok(())}
+    else {Err(ConstructError::WeightOutOfRange)}
+}
+
+/// Checks that a margin is greater than -0.5.
+fn check_margin<T: Float>(m: T) -> Result<(), ConstructError>
+    where f64: Cast<T>
+{
+    if m > (-0.5).cast() {Ok(())}
+    else {Err(ConstructError::MarginOutOfRange)}
+}
+
+/// Checks that a radius is zero or positive.
+fn check_radius<T: Float>(radius: T) -> Result<(), ConstructError> {
+    if radius >= Zero::zero() {Ok(())}
+    else {Err(ConstructError::NegativeRadius)}
+}
+
+/// Same as `con`, but checks that the weight is greater than 0 and less than 1.
+pub fn try_con<T: Float>(w: T, a: Fn1<T>, b: Fn1<T>) -> Result<Fn1<T>, ConstructError> {
+    check_weight(w)?;
+    Ok(con(w, a, b))
+}
+
+/// Same as `conx2`, but checks that the weight is greater than 0 and less than 1.
+pub fn try_conx2<T: Float>(wx: T, a: Fn2<T>, b: Fn2<T>) -> Result<Fn2<T>, ConstructError> {
+    check_weight(wx)?;
+    Ok(conx2(wx, a, b))
+}
+
+/// Same as `cony2`, but checks that the weight is greater than 0 and less than 1.
+pub fn try_cony2<T: Float>(wy: T, a: Fn2<T>, b: Fn2<T>) -> Result<Fn2<T>, ConstructError> {
+    check_weight(wy)?;
+    Ok(cony2(wy, a, b))
+}
+
+/// Same as `conx3`, but checks that the weight is greater than 0 and less than 1.
+pub fn try_conx3<T: Float>(wx: T, a: Fn3<T>, b: Fn3<T>) -> Result<Fn3<T>, ConstructError> {
+    check_weight(wx)?;
+    Ok(conx3(wx, a, b))
+}
+
+/// Same as `cony3`, but checks that the weight is greater than 0 and less than 1.
+pub fn try_cony3<T: Float>(wy: T, a: Fn3<T>, b: Fn3<T>) -> Result<Fn3<T>, ConstructError> {
+    check_weight(wy)?;
+    Ok(cony3(wy, a, b))
+}
+
+/// Same as `conz3`, but checks that the weight is greater than 0 and less than 1.
+pub fn try_conz3<T: Float>(wz: T, a: Fn3<T>, b: Fn3<T>) -> Result<Fn3<T>, ConstructError> {
+    check_weight(wz)?;
+    Ok(conz3(wz, a, b))
+}
+
+/// Same as `margin1`, but checks that the margin is greater than -0.5.
+pub fn try_margin1<T: Float>(m: T, a: Fn1<T>) -> Result<Fn1<T>, ConstructError>
+    where f64: Cast<T>
+{
+    check_margin(m)?;
+    Ok(margin1(m, a))
+}
+
+/// Same as `margin2`, but checks that the margin is greater than -0.5.
+pub fn try_margin2<T: Float>(m: T, a: Fn2<T>) -> Result<Fn2<T>, ConstructError>
+    where f64: Cast<T>
+{
+    check_margin(m)?;
+    Ok(margin2(m, a))
+}
+
+/// Same as `margin3`, but checks that the margin is greater than -0.5.
+pub fn try_margin3<T: Float>(m: T, a: Fn3<T>) -> Result<Fn3<T>, ConstructError>
+    where f64: Cast<T>
+{
+    check_margin(m)?;
+    Ok(margin3(m, a))
+}
+
+/// Same as `circle`, but checks that the radius is zero or positive.
+pub fn try_circle<T: Float>(center: [T; 3], radius: T) -> Result<Fn2<T>, ConstructError>
+    where f64: Cast<T>
+{
+    check_radius(radius)?;
+    Ok(circle(center, radius))
+}
+
+/// Same as `sphere`, but checks that the radius is zero or positive.
+pub fn try_sphere<T: Float>(center: [T; 3], radius: T) -> Result<Fn3<T>, ConstructError>
+    where f64: Cast<T>
+{
+    check_radius(radius)?;
+    Ok(sphere(center, radius))
+}
+
+/// Computes two unit vectors perpendicular to an axis and to each other,
+/// such that their cross product is along the axis.
+///
+/// For the z axis, this returns the x and y axis.
+/// A zero axis is treated as the z axis.
+fn axis_basis<T: Float>(axis: [T; 3]) -> ([T; 3], [T; 3])
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let n = normalize3(axis).unwrap_or([_0, _0, _1]);
+    // Picks a helper axis far from the axis to avoid a short cross product.
+    let helper = if n[1] * n[1] < 0.5.cast() {[_0, _1, _0]} else {[_1, _0, _0]};
+    let u = normalize3(cross3(helper, n)).unwrap_or([_1, _0, _0]);
+    let v = cross3(n, u);
+    (u, v)
+}
+
+/// Creates a helix located at a center and with a radius.
+///
+/// The input starts at the bottom and ends at the top after `turns` revolutions
+/// around the z axis, advancing `pitch` along the z axis per revolution.
+/// The angle starts along the x axis, like `circle`.
+pub fn helix<T: Float>(center: [T; 3], radius: T, pitch: T, turns: T) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let two_pi = TAU.cast();
+    return Arc::new(move |t| {
+        let angle = t * turns * two_pi;
+        [
+            center[0] + radius * angle.cos(),
+            center[1] + radius * angle.sin(),
+            center[2] + pitch * turns * t,
+        ]
+    })
+}
+
+/// Creates an arc located at a center and with a radius.
+///
+/// The input maps linearly from the start angle to the end angle, given in radians.
+/// When the end angle is less than the start angle, the arc is traversed backwards.
+///
+/// The arc is flat along the z axis, like `circle`.
+/// A full turn from 0 to 2π matches `y2(1.0, circle(center, radius))`.
+pub fn arc<T: Float>(center: [T; 3], radius: T, start_angle: T, end_angle: T) -> Fn1<T> {
+    return Arc::new(move |t| {
+        let angle = start_angle + (end_angle - start_angle) * t;
+        [
+            center[0] + radius * angle.cos(),
+            center[1] + radius * angle.sin(),
+            center[2]
+        ]
+    })
+}
+
+/// Bezier curve of any degree, evaluated with de Casteljau's algorithm.
+///
+/// Two points give a line, three points give a quadratic bezier curve, and so on.
+/// A single point gives a constant function, and no points gives the origin.
+///
+/// Notice that `cbez` blends two lines between its control points,
+/// which is not the same curve as `bezn` with four points.
+pub fn bezn<T: Float>(points: &[[T; 3]]) -> Fn1<T> {
+    let points = points.to_vec();
+    return Arc::new(move |t| {
+        if points.is_empty() {return [Zero::zero(); 3]}
+        let mut p = points.clone();
+        for n in (1..p.len()).rev() {
+            for i in 0..n {
+                p[i] = add3(p[i], scale3(sub3(p[i + 1], p[i]), t));
+            }
+        }
+        p[0]
+    })
+}
+
+/// Cubic Hermite curve from endpoints and the tangents at the endpoints.
+///
+/// Passes through `p0` at `t = 0` and `p1` at `t = 1`,
+/// with derivative `m0` and `m1` respectively.
+///
+/// This is the same curve as the true cubic Bezier
+/// `bezn(&[p0, p0 + m0 / 3, p1 - m1 / 3, p1])`.
+/// Notice that `cbez` is not a true cubic Bezier,
+/// so it does not match for the same control points.
+pub fn hermite<T: Float>(
+    p0: [T; 3],
+    m0: [T; 3],
+    p1: [T; 3],
+    m1: [T; 3],
+) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let _1: T = One::one();
+    let _2: T = 2.0.cast();
+    let _3: T = 3.0.cast();
+    return Arc::new(move |t| {
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let h00 = _2 * t3 - _3 * t2 + _1;
+        let h10 = t3 - _2 * t2 + t;
+        let h01 = _3 * t2 - _2 * t3;
+        let h11 = t3 - t2;
+        add3(add3(scale3(p0, h00), scale3(m0, h10)),
+             add3(scale3(p1, h01), scale3(m1, h11)))
+    })
+}
+
+/// Catmull-Rom spline passing through a list of points.
+///
+/// The input passes through point `i` at `i / (n - 1)` where `n` is the number of points.
+/// The first and last points are duplicated to control the ends.
+/// Two points give a line, a single point gives a constant function,
+/// and no points gives the origin.
+pub fn catmull_rom<T: Float>(points: &[[T; 3]]) -> Fn1<T>
+    where f64: Cast<T>
+{
+    catmull_rom_alpha(Zero::zero(), points)
+}
+
+/// Centripetal Catmull-Rom spline passing through a list of points.
+///
+/// Same as `catmull_rom`, but the knots are spaced by the square root of
+/// the distance between points.
+/// This avoids cusps and self-intersections at sharp turns.
+pub fn catmull_rom_centripetal<T: Float>(points: &[[T; 3]]) -> Fn1<T>
+    where f64: Cast<T>
+{
+    catmull_rom_alpha(0.5.cast(), points)
+}
+
+/// Catmull-Rom spline with knots spaced by the distance between points raised to `alpha`.
+fn catmull_rom_alpha<T: Float>(alpha: T, points: &[[T; 3]]) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let n = points.len();
+    if n == 2 {return lin(points[0], points[1])}
+    let points = points.to_vec();
+    return Arc::new(move |t| {
+        if n == 0 {return [Zero::zero(); 3]}
+        if n == 1 {return points[0]}
+        let (i, s) = split(t, n - 1);
+        let p0 = points[if i == 0 {0} else {i - 1}];
+        let p3 = points[(i + 2).min(n - 1)];
+        catmull_rom_segment(alpha, p0, points[i], points[i + 1], p3, s)
+    })
+}
+
+/// Closed Catmull-Rom spline passing through a list of points.
+///
+/// Same as `catmull_rom`, but the last point connects back to the first point,
+/// with the control points wrapping around the ends.
+/// The input 0 and 1 both give the first point, with the same tangent,
+/// such that the loop is smooth at the seam.
+pub fn catmull_rom_closed<T: Float>(points: &[[T; 3]]) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let n = points.len();
+    let points = points.to_vec();
+    return Arc::new(move |t| {
+        if n == 0 {return [Zero::zero(); 3]}
+        if n == 1 {return points[0]}
+        let (i, s) = split(t, n);
+        let p = |k: usize| points[k % n];
+        catmull_rom_segment(Zero::zero(), p(i + n - 1), p(i), p(i + 1), p(i + 2), s)
+    })
+}
+
+/// Evaluates a Catmull-Rom segment between `p1` and `p2`.
+///
+/// Uses the Barry and Goldman pyramidal formulation
+/// with knots spaced by the distance between points raised to `alpha`.
+fn catmull_rom_segment<T: Float>(
+    alpha: T,
+    p0: [T; 3],
+    p1: [T; 3],
+    p2: [T; 3],
+    p3: [T; 3],
+    s: T
+) -> [T; 3] {
+    fn lerp<T: Float>(a: [T; 3], b: [T; 3], t0: T, t1: T, t: T) -> [T; 3] {
+        if t1 == t0 {b} else {add3(a, scale3(sub3(b, a), (t - t0) / (t1 - t0)))}
+    }
+
+    let t0: T = Zero::zero();
+    let t1 = t0 + len3(sub3(p1, p0)).powf(alpha);
+    let t2 = t1 + len3(sub3(p2, p1)).powf(alpha);
+    let t3 = t2 + len3(sub3(p3, p2)).powf(alpha);
+    if t2 == t1 {return p1}
+    let t = t1 + (t2 - t1) * s;
+    let a1 = lerp(p0, p1, t0, t1, t);
+    let a2 = lerp(p1, p2, t1, t2, t);
+    let a3 = lerp(p2, p3, t2, t3, t);
+    let b1 = lerp(a1, a2, t0, t2, t);
+    let b2 = lerp(a2, a3, t1, t3, t);
+    lerp(b1, b2, t1, t2, t)
+}
+
+/// Uniform cubic B-spline over a list of control points.
+///
+/// The input spans the whole curve, with `n - 3` segments for `n` control points.
+/// Unlike a Bezier curve, the B-spline does not pass through its end points,
+/// and generally not through the interior control points either,
+/// but stays within the convex hull of the control points.
+///
+/// With fewer than four control points, it falls back to a Bezier curve of lower degree,
+/// which is a line for two points.
+pub fn bspline<T: Float>(points: &[[T; 3]]) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let n = points.len();
+    if n < 4 {return bezn(points)}
+    let _1: T = One::one();
+    let _3: T = 3.0.cast();
+    let _4: T = 4.0.cast();
+    let _6: T = 6.0.cast();
+    let points = points.to_vec();
+    return Arc::new(move |t| {
+        let (i, s) = split(t, n - 3);
+        let s2 = s * s;
+        let s3 = s2 * s;
+        let u = _1 - s;
+        let b0 = u * u * u / _6;
+        let b1 = (_3 * s3 - _6 * s2 + _4) / _6;
+        let b2 = (-_3 * s3 + _3 * s2 + _3 * s + _1) / _6;
+        let b3 = s3 / _6;
+        add3(add3(scale3(points[i], b0), scale3(points[i + 1], b1)),
+             add3(scale3(points[i + 2], b2), scale3(points[i + 3], b3)))
+    })
+}
+
+/// Computes the unit tangent of a curve.
+///
+/// Uses central differences with step `eps`,
+/// falling back to one-sided differences at the ends.
+/// At cusps where the curve stops moving, the derivative is zero and so is the tangent.
+/// Near cusps the tangent is sensitive to `eps` and might change direction abruptly.
+pub fn tangent1<T: Float>(eps: T, a: Fn1<T>) -> Fn1<T> {
+    return Arc::new(move |t| {
+        normalize3(diff1(eps, &a, t)).unwrap_or([Zero::zero(); 3])
+    })
+}
+
+/// Computes the finite difference of a curved quad along an axis,
+/// using one-sided differences at the edges.
+fn diff2<T: Float>(eps: T, a: &Fn2<T>, t: [T; 2], axis: usize) -> [T; 3] {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let (mut t0, mut t1) = (t, t);
+    t0[axis] = (t[axis] - eps).max(_0);
+    t1[axis] = (t[axis] + eps).min(_1);
+    scale3(sub3(a(t1), a(t0)), _1 / (t1[axis] - t0[axis]))
+}
+
+/// Computes the unit normal of a curved quad at some input.
+///
+/// Returns `None` when the partial derivatives are parallel or zero.
+fn normal_at2<T: Float>(eps: T, a: &Fn2<T>, t: [T; 2]) -> Option<[T; 3]> {
+    normalize3(cross3(diff2(eps, a, t, 0), diff2(eps, a, t, 1)))
+}
+
+/// Computes the unit normal of a curved quad.
+///
+/// The normal is the cross product of the partial derivatives along the first and
+/// second input argument, computed with central differences with step `eps`.
+/// One-sided differences are used at the edges.
+/// Where the surface is degenerate, e.g. at the poles of a sphere,
+/// the normal is computed a step `eps` inside the edge instead.
+/// If the surface is degenerate there too, the normal is zero.
+pub fn normal2<T: Float>(eps: T, a: Fn2<T>) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let _05: T = 0.5.cast();
+    return Arc::new(move |t| {
+        normal_at2(eps, &a, t).or_else(|| {
+            let inside = |x: T| if x < _05 {x + eps} else {x - eps};
+            normal_at2(eps, &a, [inside(t[0]), inside(t[1])])
+        }).unwrap_or([Zero::zero(); 3])
+    })
+}
+
+/// Displaces a curved quad along its normal by a scalar field.
+///
+/// The normal is computed like `normal2` with step `eps`,
+/// so where it is zero, e.g. at degenerate poles, the point stays in place.
+/// Points where the field is zero are unchanged.
+pub fn displace2<T: Float>(
+    eps: T,
+    field: Arc<Fn([T; 2]) -> T + Sync + Send>,
+    a: Fn2<T>
+) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let normal = normal2(eps, a.clone());
+    return Arc::new(move |t| {
+        let d = field(t);
+        if d == _0 {a(t)} else {add3(a(t), scale3(normal(t), d))}
+    })
+}
+
+/// Thickens a curved quad into a solid shell.
+///
+/// The third input argument moves from the surface at 0 to the surface
+/// offset by `thickness` along the normal at 1.
+/// The normal is computed like `normal2` with step `eps`,
+/// and a negative thickness offsets to the other side.
+/// Concave parts with a radius of curvature smaller than the thickness fold over.
+pub fn shell<T: Float>(eps: T, thickness: T, a: Fn2<T>) -> Fn3<T>
+    where f64: Cast<T>
+{
+    let normal = normal2(eps, a.clone());
+    return Arc::new(move |t| {
+        let uv = [t[0], t[1]];
+        add3(a(uv), scale3(normal(uv), thickness * t[2]))
+    })
+}
+
+/// Computes the approximate length of a curve.
+///
+/// Sums the chord lengths of `samples` segments of equal input size,
+/// using at least one segment.
+/// Since chords cut corners, the result never exceeds the true length.
+/// For smooth curves the error shrinks with the square of the number of segments,
+/// and straight lines are exact for any number of segments.
+pub fn arc_length<T: Float>(samples: usize, a: &Fn1<T>) -> T
+    where f64: Cast<T>
+{
+    let n = samples.max(1);
+    let mut sum: T = Zero::zero();
+    let mut prev = a(Zero::zero());
+    for i in 1..n + 1 {
+        let pos = a((i as f64 / n as f64).cast());
+        sum += len3(sub3(pos, prev));
+        prev = pos;
+    }
+    sum
+}
+
+/// Reparameterizes a curve by arc length.
+///
+/// The curve is sampled once at `samples` segments of equal input size,
+/// building a table of accumulated lengths.
+/// Equal steps of input to the new curve give approximately equal steps of length.
+pub fn reparam_by_length<T: Float>(samples: usize, a: Fn1<T>) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let n = samples.max(1);
+    let mut lengths: Vec<T> = Vec::with_capacity(n + 1);
+    let mut sum: T = Zero::zero();
+    let mut prev = a(Zero::zero());
+    lengths.push(sum);
+    for i in 1..n + 1 {
+        let pos = a((i as f64 / n as f64).cast());
+        sum += len3(sub3(pos, prev));
+        lengths.push(sum);
+        prev = pos;
+    }
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let n_t: T = (n as f64).cast();
+    return Arc::new(move |t| {
+        if sum == _0 {return a(t)}
+        let target = t * sum;
+        // Find the last sample with accumulated length below target.
+        let lo = last_where(n, |i| lengths[i] <= target);
+        let seg = lengths[lo + 1] - lengths[lo];
+        let f = if seg == _0 {_0} else {(target - lengths[lo]) / seg};
+        let lo_t: T = (lo as f64).cast();
+        a(((lo_t + f) / n_t).min(_1))
+    })
+}
+
+/// Remaps the input of a curve, e.g. with an easing function from the `ease` module.
+pub fn reparam1<T: Float>(f: Arc<Fn(T) -> T + Sync + Send>, a: Fn1<T>) -> Fn1<T> {
+    return Arc::new(move |t| a(f(t)))
+}
+
+/// Caches samples of a curve and interpolates linearly between them.
+///
+/// The curve is sampled at `resolution` evenly spaced inputs when calling this function,
+/// using at least 2 samples such that the end points are exact.
+/// This trades accuracy for speed when the curve is expensive to evaluate.
+/// The error is approximately proportional to the curvature divided by the
+/// square of the resolution.
+pub fn cache1<T: Float>(resolution: usize, a: Fn1<T>) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let n = resolution.max(2);
+    let samples: Vec<[T; 3]> = (0..n)
+        .map(|i| a((i as f64 / (n - 1) as f64).cast()))
+        .collect();
+    return Arc::new(move |t| {
+        let (i, f) = split(t, n - 1);
+        // Exact at both ends of the segment.
+        add3(scale3(samples[i], <T as One>::one() - f), scale3(samples[i + 1], f))
+    })
+}
+
+/// Iterates over `n` evenly spaced points of a curve.
+///
+/// The first point is at 0 and the last point is at 1, with step `1 / (n - 1)`.
+/// A single sample is taken at 0, and no samples yield nothing.
+pub fn samples1<T: Float>(n: usize, a: Fn1<T>) -> impl Iterator<Item = [T; 3]>
+    where f64: Cast<T>
+{
+    (0..n).map(move |i| a(grid_param(i, n)))
+}
+
+/// Computes the distance from a point to the line segment between two points.
+fn segment_dist<T: Float>(p: [T; 3], a: [T; 3], b: [T; 3]) -> T {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let ab = sub3(b, a);
+    let len_sq = dot3(ab, ab);
+    let s = if len_sq == _0 {_0} else {(dot3(sub3(p, a), ab) / len_sq).max(_0).min(_1)};
+    len3(sub3(p, add3(a, scale3(ab, s))))
+}
+
+/// Flattens the part of a curve between two inputs, pushing all points but the first.
+fn flatten_range<T: Float>(
+    tol: T,
+    a: &Fn1<T>,
+    (t0, p0): (T, [T; 3]),
+    (t1, p1): (T, [T; 3]),
+    depth: u32,
+    out: &mut Vec<[T; 3]>
+)
+    where f64: Cast<T>
+{
+    let _025: T = 0.25.cast();
+    let _05: T = 0.5.cast();
+    let _075: T = 0.75.cast();
+    let dt = t1 - t0;
+    let tm = t0 + dt * _05;
+    let pm = a(tm);
+    let flat = depth == 0 || [_025, _075].iter().fold(
+        segment_dist(pm, p0, p1) <= tol,
+        |flat, &s| flat && segment_dist(a(t0 + dt * s), p0, p1) <= tol
+    );
+    if flat {
+        out.push(p1);
+    } else {
+        flatten_range(tol, a, (t0, p0), (tm, pm), depth - 1, out);
+        flatten_range(tol, a, (tm, pm), (t1, p1), depth - 1, out);
+    }
+}
+
+/// Flattens a curve to a polyline within a tolerance.
+///
+/// The input range is split in half recursively until the points at
+/// 1/4, 1/2 and 3/4 of a segment are within `tol` distance of its chord.
+/// Straight parts get few points and curvy parts get many.
+/// Features smaller than a quarter of a segment might be missed.
+/// The recursion stops at 16 levels, giving at most 65537 points.
+pub fn flatten1<T: Float>(tol: T, a: &Fn1<T>) -> Vec<[T; 3]>
+    where f64: Cast<T>
+{
+    let (t0, t1): (T, T) = (Zero::zero(), One::one());
+    let p0 = a(t0);
+    let mut out = vec![p0];
+    flatten_range(tol, a, (t0, p0), (t1, a(t1)), 16, &mut out);
+    out
+}
+
+/// Closes a curve whose end points are within a tolerance.
+///
+/// The gap between the end and the start is spread linearly over the curve,
+/// such that the start is unchanged and the end is exactly the start.
+/// Curves with a larger gap are returned unchanged.
+pub fn close1<T: Float>(tol: T, a: Fn1<T>) -> Fn1<T> {
+    let _1: T = One::one();
+    let start = a(Zero::zero());
+    let gap = sub3(start, a(_1));
+    if len3(gap) > tol {return a}
+    return Arc::new(move |t| if t >= _1 {start} else {add3(a(t), scale3(gap, t))})
+}
+
+/// Normalizes a grid index, such that the first index is 0 and the last index is 1.
+fn grid_param<T: Float>(i: usize, n: usize) -> T
+    where f64: Cast<T>
+{
+    if n <= 1 {Zero::zero()} else {(i as f64 / (n - 1) as f64).cast()}
+}
+
+/// Rounds down, using the remainder since `Float` has no `floor`.
+fn floor<T: Float>(x: T) -> T {
+    let _1: T = One::one();
+    let f = x - x % _1;
+    if f > x {f - _1} else {f}
+}
+
+/// Computes the smallest box containing all points, as `(min, max)` corners.
+///
+/// Panics if there are no points.
+fn bounds_of<T: Float, I: Iterator<Item = [T; 3]>>(mut points: I) -> ([T; 3], [T; 3]) {
+    let first = points.next().expect("bounds_of: no points");
+    points.fold((first, first), |(min, max), p| (
+        [min[0].min(p[0]), min[1].min(p[1]), min[2].min(p[2])],
+        [max[0].max(p[0]), max[1].max(p[1]), max[2].max(p[2])],
+    ))
+}
+
+/// Computes the axis-aligned bounding box of a curved quad, as `(min, max)` corners.
+///
+/// The curved quad is sampled on a grid of `nu x nv` points,
+/// using at least 1 sample in each direction.
+/// Since only the samples are checked,
+/// curved surfaces might bulge slightly outside the box.
+pub fn bounds2<T: Float>(nu: usize, nv: usize, a: &Fn2<T>) -> ([T; 3], [T; 3])
+    where f64: Cast<T>
+{
+    bounds_of(sample_grid(nu.max(1), nv.max(1), a).into_iter())
+}
+
+/// Computes the axis-aligned bounding box of a curved cube, as `(min, max)` corners.
+///
+/// The curved cube is sampled on a grid of `nu x nv x nw` points,
+/// using at least 1 sample in each direction.
+/// Since only the samples are checked,
+/// curved surfaces might bulge slightly outside the box.
+pub fn bounds3<T: Float>(nu: usize, nv: usize, nw: usize, a: &Fn3<T>) -> ([T; 3], [T; 3])
+    where f64: Cast<T>
+{
+    let (nu, nv, nw) = (nu.max(1), nv.max(1), nw.max(1));
+    bounds_of((0..nu * nv * nw).map(|k| a([
+        grid_param(k / (nv * nw), nu),
+        grid_param(k / nw % nv, nv),
+        grid_param(k % nw, nw),
+    ])))
+}
+
+/// Revolves a profile around the z axis.
+///
+/// The first input argument is the angle, rotating 360 degrees around the z axis.
+/// The second input argument moves along the profile.
+/// The profile is assumed to lie in the xz-plane,
+/// where the distance from the z axis becomes the radius at that height.
+/// The seam at the start and end of the angle matches exactly.
+pub fn revolve<T: Float>(profile: Fn1<T>) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let two_pi = TAU.cast();
+    let _1: T = One::one();
+    return Arc::new(move |t| {
+        let p = profile(t[1]);
+        let radius = (p[0] * p[0] + p[1] * p[1]).sqrt();
+        // Use the same angle at the end as at the start to close the seam.
+        let u = if t[0] == _1 {Zero::zero()} else {t[0]};
+        let angle = u * two_pi;
+        [radius * angle.cos(), radius * angle.sin(), p[2]]
+    })
+}
+
+/// Lofts a surface through several sections.
+///
+/// The first input argument moves along the sections.
+/// The second input argument interpolates linearly between adjacent sections,
+/// which are evenly spaced such that section `i` is at `i / (n - 1)`.
+/// A single section gives a surface that is constant along the second input argument.
+///
+/// Panics if there are no sections.
+pub fn loft<T: Float>(sections: Vec<Fn1<T>>) -> Fn2<T>
+    where f64: Cast<T>
+{
+    assert!(!sections.is_empty(), "loft: there must be at least one section");
+    let n = sections.len();
+    return Arc::new(move |t| {
+        if n == 1 {return sections[0](t[0])}
+        let (i, f) = split(t[1], n - 1);
+        let a = sections[i](t[0]);
+        let b = sections[i + 1](t[0]);
+        add3(scale3(a, <T as One>::one() - f), scale3(b, f))
+    })
+}
+
+/// Creates a superellipse located at a center, with radii `a` and `b` along x and y.
+///
+/// The curve satisfies `|x/a|^n + |y/b|^n = 1`.
+/// A superellipse with `n = 2` is an ellipse,
+/// and as `n` grows it approaches a rectangle.
+///
+/// The input is the angle starting at 0, rotating 360 degrees around the center.
+/// The curve is flat along the z axis.
+pub fn superellipse<T: Float>(center: [T; 3], a: T, b: T, n: T) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let two_pi = TAU.cast();
+    let e = 2.0.cast() / n;
+    return Arc::new(move |t| {
+        let angle = t * two_pi;
+        let (cos, sin) = (angle.cos(), angle.sin());
+        [
+            center[0] + a * cos.signum() * (cos * cos.signum()).powf(e),
+            center[1] + b * sin.signum() * (sin * sin.signum()).powf(e),
+            center[2]
+        ]
+    })
+}
+
+/// Creates a Lissajous curve located at a center.
+///
+/// Each coordinate is `center + amp * sin(2π * freq * t + phase)`,
+/// with the phase in radians.
+/// A frequency of 1 completes one period as the input goes from 0 to 1,
+/// so the curve is closed when all frequencies are integers.
+pub fn lissajous<T: Float>(center: [T; 3], amp: [T; 3], freq: [T; 3], phase: [T; 3]) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let two_pi: T = TAU.cast();
+    return Arc::new(move |t| {
+        let f = |i: usize| center[i] + amp[i] * (two_pi * freq[i] * t + phase[i]).sin();
+        [f(0), f(1), f(2)]
+    })
+}
+
+/// Creates a trefoil knot centered at the origin.
+///
+/// The input goes once around the closed knot, starting and ending at the same point.
+/// The knot stays within a distance of `3 * scale` from the origin.
+pub fn trefoil<T: Float>(scale: T) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let two_pi: T = TAU.cast();
+    let _2: T = 2.0.cast();
+    let _3: T = 3.0.cast();
+    return Arc::new(move |t| {
+        let angle = t * two_pi;
+        [
+            scale * (angle.sin() + _2 * (_2 * angle).sin()),
+            scale * (angle.cos() - _2 * (_2 * angle).cos()),
+            -scale * (_3 * angle).sin(),
+        ]
+    })
+}
+
+/// Creates a Möbius strip centered at the origin.
+///
+/// The first input argument goes around the loop in the xy-plane.
+/// The second input argument goes across the width of the strip,
+/// which makes a half twist over a full loop.
+/// The strip closes onto itself flipped, such that `[1, v]` matches `[0, 1 - v]`.
+///
+/// The strip is non-orientable, so normals computed from the input,
+/// e.g. with `normal2`, point in opposite directions on each side of the seam.
+pub fn mobius<T: Float>(radius: T, width: T) -> Fn2<T>
+    where f64: Cast<T>
+{
+    let two_pi: T = TAU.cast();
+    let _05: T = 0.5.cast();
+    return Arc::new(move |t| {
+        let angle = t[0] * two_pi;
+        let half = _05 * angle;
+        let s = (t[1] - _05) * width;
+        let r = radius + s * half.cos();
+        [r * angle.cos(), r * angle.sin(), s * half.sin()]
+    })
+}
+
+/// Finds the input of a curve at the nearest point to a target.
+///
+/// The curve is scanned at `samples` segments of equal input size,
+/// using at least one segment, followed by a golden-section search
+/// around the nearest sample.
+/// For targets off the curve, this returns the input of the nearest point.
+/// When several points are almost equally near, the coarse scan decides which one
+/// is refined, so too few samples might miss the nearest point on curvy curves.
+pub fn nearest_t1<T: Float>(samples: usize, target: [T; 3], a: &Fn1<T>) -> T
+    where f64: Cast<T>
+{
+    let n = samples.max(1);
+    let dist = |t: T| {
+        let d = sub3(a(t), target);
+        dot3(d, d)
+    };
+    let param = |i: usize| -> T {(i as f64 / n as f64).cast()};
+    let mut best = 0;
+    let mut best_dist = dist(param(0));
+    for i in 1..n + 1 {
+        let d = dist(param(i));
+        if d < best_dist {
+            best = i;
+            best_dist = d;
+        }
+    }
+
+    // Golden-section search between the neighbors of the nearest sample.
+    let inv_phi: T = 0.6180339887498949.cast();
+    let (mut lo, mut hi) = (param(best.max(1) - 1), param((best + 1).min(n)));
+    let mut x1 = hi - inv_phi * (hi - lo);
+    let mut x2 = lo + inv_phi * (hi - lo);
+    let (mut d1, mut d2) = (dist(x1), dist(x2));
+    for _ in 0..64 {
+        if d1 < d2 {
+            hi = x2;
+            x2 = x1;
+            d2 = d1;
+            x1 = hi - inv_phi * (hi - lo);
+            d1 = dist(x1);
+        } else {
+            lo = x1;
+            x1 = x2;
+            d1 = d2;
+            x2 = lo + inv_phi * (hi - lo);
+            d2 = dist(x2);
+        }
+    }
+    let t = if d1 < d2 {x1} else {x2};
+    // The end points are not evaluated by the search.
+    let t0 = param(best.max(1) - 1);
+    let t1 = param((best + 1).min(n));
+    [t0, t1].iter().fold(t, |t, &s| if dist(s) < dist(t) {s} else {t})
+}
+
+/// Computes the Jacobian matrix of a volume map at some input.
+///
+/// Element `[i][j]` is the partial derivative of output coordinate `i`
+/// with respect to input argument `j`, such that each column is a partial derivative.
+/// The derivatives are computed with central differences with step `eps`,
+/// using one-sided differences at the edges of the input range.
+pub fn jacobian3<T: Float>(eps: T, a: &Fn3<T>, t: [T; 3]) -> [[T; 3]; 3] {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let mut cols = [[_0; 3]; 3];
+    for (axis, col) in cols.iter_mut().enumerate() {
+        let (mut t0, mut t1) = (t, t);
+        t0[axis] = (t[axis] - eps).max(_0);
+        t1[axis] = (t[axis] + eps).min(_1);
+        *col = scale3(sub3(a(t1), a(t0)), _1 / (t1[axis] - t0[axis]));
+    }
+    vecmath::mat3_transposed(cols)
+}
+
+/// Computes the Jacobian determinant of a volume map at some input.
+///
+/// This is the local change of volume from the input to the output.
+/// It is negative where the volume map is inverted, e.g. when the solid folds over itself,
+/// and zero where it is degenerate, e.g. at the center of a sphere.
+pub fn jacobian_det3<T: Float>(eps: T, a: &Fn3<T>, t: [T; 3]) -> T {
+    vecmath::mat3_det(jacobian3(eps, a, t))
+}
+
+/// Samples a volume map on a grid and approximates each cell by a parallelepiped.
+///
+/// Returns the center and the signed volume of each of the `(nu - 1) x (nv - 1) x (nw - 1)` cells,
+/// using at least 2 samples in each direction.
+/// The edges of the parallelepiped are the averages of the four cell edges along each direction.
+fn sample_cells3<T: Float>(nu: usize, nv: usize, nw: usize, a: &Fn3<T>) -> Vec<([T; 3], T)>
+    where f64: Cast<T>
+{
+    let (nu, nv, nw) = (nu.max(2), nv.max(2), nw.max(2));
+    let _025: T = 0.25.cast();
+    let _0125: T = 0.125.cast();
+    let mut verts = Vec::with_capacity(nu * nv * nw);
+    for i in 0..nu {
+        for j in 0..nv {
+            for k in 0..nw {
+                verts.push(a([grid_param(i, nu), grid_param(j, nv), grid_param(k, nw)]));
+            }
+        }
+    }
+    let mut cells = Vec::with_capacity((nu - 1) * (nv - 1) * (nw - 1));
+    for i in 0..nu - 1 {
+        for j in 0..nv - 1 {
+            for k in 0..nw - 1 {
+                let p = |di: usize, dj: usize, dk: usize| verts[((i + di) * nv + j + dj) * nw + k + dk];
+                let mut edges = [[Zero::zero(); 3]; 3];
+                let mut center = [Zero::zero(); 3];
+                for &(x, y) in &[(0, 0), (0, 1), (1, 0), (1, 1)] {
+                    edges[0] = add3(edges[0], sub3(p(1, x, y), p(0, x, y)));
+                    edges[1] = add3(edges[1], sub3(p(x, 1, y), p(x, 0, y)));
+                    edges[2] = add3(edges[2], sub3(p(x, y, 1), p(x, y, 0)));
+                    center = add3(center, add3(p(0, x, y), p(1, x, y)));
+                }
+                let edges = [scale3(edges[0], _025), scale3(edges[1], _025), scale3(edges[2], _025)];
+                let volume = dot3(edges[0], cross3(edges[1], edges[2]));
+                cells.push((scale3(center, _0125), volume));
+            }
+        }
+    }
+    cells
+}
+
+/// Triangulates a curved quad like `tri_mesh` and computes each triangle's center and area.
+fn sample_tris2<T: Float>(nu: usize, nv: usize, a: &Fn2<T>) -> Vec<([T; 3], T)>
+    where f64: Cast<T>
+{
+    let _05: T = 0.5.cast();
+    let third: T = (1.0 / 3.0).cast();
+    let (verts, tris) = tri_mesh(nu, nv, [false, false], a);
+    tris.iter().map(|tri| {
+        let (p0, p1, p2) = (verts[tri[0] as usize], verts[tri[1] as usize], verts[tri[2] as usize]);
+        let center = scale3(add3(add3(p0, p1), p2), third);
+        (center, len3(cross3(sub3(p1, p0), sub3(p2, p0))) * _05)
+    }).collect()
+}
+
+/// Computes the approximate centroid of a curved quad.
+///
+/// The curved quad is triangulated like `tri_mesh` on a grid of `nu x nv` points,
+/// and the triangle centers are averaged weighted by triangle area,
+/// such that the result does not depend on how densely the input is mapped.
+/// Returns the average of the triangle centers if the total area is zero.
+pub fn centroid2<T: Float>(nu: usize, nv: usize, a: &Fn2<T>) -> [T; 3]
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let tris = sample_tris2(nu, nv, a);
+    let mut sum = [_0; 3];
+    let mut total = _0;
+    for &(center, area) in &tris {
+        sum = add3(sum, scale3(center, area));
+        total += area;
+    }
+    if total == _0 {
+        let n: T = (tris.len() as f64).cast();
+        return scale3(tris.iter().fold([_0; 3], |s, &(c, _)| add3(s, c)), _1 / n);
+    }
+    scale3(sum, _1 / total)
+}
+
+/// Computes the approximate centroid of a volume map.
+///
+/// The volume map is sampled on a grid of `nu x nv x nw` points,
+/// and the cell centers are averaged weighted by cell volume,
+/// such that the result does not depend on how densely the input is mapped.
+/// Returns the average of the cell centers if the total volume is zero.
+pub fn centroid3<T: Float>(nu: usize, nv: usize, nw: usize, a: &Fn3<T>) -> [T; 3]
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let cells = sample_cells3(nu, nv, nw, a);
+    let mut sum = [_0; 3];
+    let mut total = _0;
+    for &(center, volume) in &cells {
+        sum = add3(sum, scale3(center, volume));
+        total += volume;
+    }
+    if total == _0 {
+        let n: T = (cells.len() as f64).cast();
+        return scale3(cells.iter().fold([_0; 3], |s, &(c, _)| add3(s, c)), _1 / n);
+    }
+    scale3(sum, _1 / total)
+}
+
+/// Computes the approximate surface area of a curved quad.
+///
+/// The curved quad is triangulated like `tri_mesh` on a grid of `nu x nv` points,
+/// and the triangle areas are summed.
+/// The result converges to the true area as the number of samples grows.
+/// Degenerate triangles, e.g. at the poles of a sphere, contribute zero area.
+pub fn surface_area<T: Float>(nu: usize, nv: usize, a: &Fn2<T>) -> T
+    where f64: Cast<T>
+{
+    sample_tris2(nu, nv, a).iter().fold(Zero::zero(), |sum, &(_, area)| sum + area)
+}
+
+/// Computes the approximate volume of a volume map.
+///
+/// The volume map is sampled on a grid of `nu x nv x nw` points, using at least 2 in each
+/// direction, and each cell is approximated by a parallelepiped spanned by its averaged edges.
+/// The signed volumes of the cells are summed, so inverted parts of the map subtract volume.
+/// Maps that are linear within each cell are exact, e.g. any `scl` of the unit cube,
+/// and for smooth maps the error shrinks with the square of the number of samples.
+pub fn volume3<T: Float>(nu: usize, nv: usize, nw: usize, a: &Fn3<T>) -> T
+    where f64: Cast<T>
+{
+    sample_cells3(nu, nv, nw, a).iter().fold(Zero::zero(), |sum, &(_, volume)| sum + volume)
+}
+
+/// Creates a hanging cable between two points.
+///
+/// The cable is a catenary in the vertical plane through both points,
+/// with gravity pointing along the negative z axis.
+/// Its length is the distance between the points plus `sag`.
+/// The input moves at constant speed horizontally, and the curve passes exactly
+/// through `a` at 0 and `b` at 1.
+///
+/// The shape parameter is found by bisection, which converges for any positive sag.
+/// As the sag approaches zero the cable approaches the straight line,
+/// and a cable that is taut, or has no horizontal extent, is a straight line.
+pub fn catenary<T: Float>(a: [T; 3], b: [T; 3], sag: T) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let _2: T = 2.0.cast();
+    let d = sub3(b, a);
+    let dir = [d[0], d[1], _0];
+    let h = len3(dir);
+    let v = d[2];
+    let len = len3(d) + sag;
+    // Solves `sinh(x) / x = r` for the half horizontal extent `x = h / (2 c)`.
+    let r = if h > _0 {(len * len - v * v).sqrt() / h} else {_0};
+    // NaN is not comparable, so it falls back to a line like a ratio of at most 1.
+    if sag <= _0 || r.partial_cmp(&_1) != Some(Greater) {return lin(a, b)}
+    let f = |x: T| x.sinh() / x;
+    let mut hi = _1;
+    while f(hi) < r {hi *= _2}
+    let mut lo = _0;
+    for _ in 0..100 {
+        let mid = (lo + hi) / _2;
+        if mid == lo || mid == hi {break}
+        if f(mid) < r {lo = mid} else {hi = mid}
+    }
+    let x = (lo + hi) / _2;
+    if x.partial_cmp(&_0) != Some(Greater) {return lin(a, b)}
+    let c = h / (_2 * x);
+    // The horizontal position of the lowest point, measured from `a`.
+    let x0 = h / _2 - c * (v / len).atanh();
+    let height = move |s: T| c * ((s - x0) / c).cosh();
+    let (za, zb) = (height(_0), height(h));
+    return Arc::new(move |t| {
+        if t <= _0 {return a}
+        if t >= _1 {return b}
+        // Corrects rounding errors such that the ends are continuous.
+        let z = height(t * h) - za * (_1 - t) - zb * t;
+        [a[0] + d[0] * t, a[1] + d[1] * t, a[2] + z + v * t]
+    })
+}
+
+/// Creates a rose curve located at a center in the xy plane.
+///
+/// The distance from the center is `radius * cos(k * θ)` with `k = n / d`.
+/// The angle `θ` goes around `d` full turns as the input goes from 0 to 1,
+/// which closes the curve when `n` and `d` are integers.
+/// When `n / d` is reduced and both are odd, the curve is closed after half the input,
+/// and the second half traces the same petals again.
+/// With `n = d = 1` the curve is a circle with diameter `radius`, touching the center.
+pub fn rose<T: Float>(center: [T; 3], radius: T, n: T, d: T) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let two_pi: T = TAU.cast();
+    return Arc::new(move |t| {
+        let angle = two_pi * d * t;
+        let r = radius * (n / d * angle).cos();
+        [center[0] + r * angle.cos(), center[1] + r * angle.sin(), center[2]]
+    })
+}
+
+/// Projects a point onto a curved quad.
+///
+/// Returns the input and the position of the nearest point found.
+/// The curved quad is sampled on a grid of `nu x nv` points, using at least 2 in each direction,
+/// and the nearest sample is refined with Gauss-Newton steps in the input space,
+/// using partial derivatives from finite differences.
+/// The result is approximate, and depends on the resolution when there are several
+/// local minima, e.g. for points near the axis of a surface of revolution.
+pub fn project2<T: Float>(nu: usize, nv: usize, point: [T; 3], a: &Fn2<T>) -> ([T; 2], [T; 3])
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let _05: T = 0.5.cast();
+    let eps: T = 0.0001.cast();
+    let (nu, nv) = (nu.max(2), nv.max(2));
+    let dist = |p: [T; 3]| {
+        let d = sub3(p, point);
+        dot3(d, d)
+    };
+    let mut best_t = [_0, _0];
+    let mut best_p = a(best_t);
+    let mut best_d = dist(best_p);
+    for i in 0..nu {
+        for j in 0..nv {
+            let t = [grid_param(i, nu), grid_param(j, nv)];
+            let p = a(t);
+            let d = dist(p);
+            if d < best_d {
+                best_t = t;
+                best_p = p;
+                best_d = d;
+            }
+        }
+    }
+
+    for _ in 0..32 {
+        let du = diff2(eps, a, best_t, 0);
+        let dv = diff2(eps, a, best_t, 1);
+        let r = sub3(point, best_p);
+        // Solves the normal equations of the linearized problem.
+        let (a00, a01, a11) = (dot3(du, du), dot3(du, dv), dot3(dv, dv));
+        let (b0, b1) = (dot3(du, r), dot3(dv, r));
+        let det = a00 * a11 - a01 * a01;
+        if det == _0 {break}
+        let mut step = [(a11 * b0 - a01 * b1) / det, (a00 * b1 - a01 * b0) / det];
+        let mut improved = false;
+        for _ in 0..8 {
+            let t = [
+                (best_t[0] + step[0]).max(_0).min(_1),
+                (best_t[1] + step[1]).max(_0).min(_1)
+            ];
+            let p = a(t);
+            let d = dist(p);
+            if d < best_d {
+                best_t = t;
+                best_p = p;
+                best_d = d;
+                improved = true;
+                break;
+            }
+            step = [step[0] * _05, step[1] * _05];
+        }
+        if !improved {break}
+    }
+    (best_t, best_p)
+}
+
+/// Moves an input of a curved quad toward the nearest point to a target by gradient descent.
+///
+/// Takes up to `iters` steps downhill on the squared distance to `target`, starting at `seed`,
+/// using partial derivatives from finite differences with step `eps`.
+/// Each step goes as far as the linearized distance decreases,
+/// and is halved until the distance decreases, stopping early otherwise.
+///
+/// The input is clamped to `[0, 1]`, and at the boundary of the domain
+/// the step slides along the edge instead of pushing out of it.
+/// Unlike `project2`, this finds the local minimum near the seed.
+pub fn descend2<T: Float>(eps: T, iters: usize, target: [T; 3], seed: [T; 2], a: &Fn2<T>) -> [T; 2]
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let _05: T = 0.5.cast();
+    let dist = |p: [T; 3]| {
+        let d = sub3(p, target);
+        dot3(d, d)
+    };
+    let mut t = [seed[0].max(_0).min(_1), seed[1].max(_0).min(_1)];
+    let mut p = a(t);
+    let mut d = dist(p);
+    for _ in 0..iters {
+        let du = diff2(eps, a, t, 0);
+        let dv = diff2(eps, a, t, 1);
+        let r = sub3(p, target);
+        let mut dir = [-dot3(du, r), -dot3(dv, r)];
+        // Removes the components pushing out of the domain.
+        for k in 0..2 {
+            if (t[k] <= _0 && dir[k] < _0) || (t[k] >= _1 && dir[k] > _0) {dir[k] = _0}
+        }
+        let jd = add3(scale3(du, dir[0]), scale3(dv, dir[1]));
+        let jj = dot3(jd, jd);
+        if jj == _0 {break}
+        let alpha = (dir[0] * dir[0] + dir[1] * dir[1]) / jj;
+        let mut step = [dir[0] * alpha, dir[1] * alpha];
+        let mut improved = false;
+        for _ in 0..8 {
+            let next = [
+                (t[0] + step[0]).max(_0).min(_1),
+                (t[1] + step[1]).max(_0).min(_1)
+            ];
+            let next_p = a(next);
+            let next_d = dist(next_p);
+            if next_d < d {
+                t = next;
+                p = next_p;
+                d = next_d;
+                improved = true;
+                break;
+            }
+            step = [step[0] * _05, step[1] * _05];
+        }
+        if !improved {break}
+    }
+    t
+}
+
+/// Computes a rotation-minimizing `(tangent, normal, binormal)` frame of a curve.
+///
+/// The frame starts at input 0 like the frame of `sweep`, but with `up` as up direction,
+/// such that the normal is perpendicular to both `up` and the tangent.
+/// It is then carried along the curve to `t` in steps of `eps` using double reflection,
+/// which rotates the frame as little as possible.
+/// Unlike the Frenet frame, it does not flip at inflection points or along straight parts.
+///
+/// Each call walks from the start of the curve, so the cost is proportional to `t / eps`,
+/// with at most a million steps.
+/// To sample frames along the whole curve, use `frames1`, which walks the curve once.
+/// Tangents use finite differences with step `eps`, and where the curve stops moving,
+/// the previous tangent is kept.
+pub fn frame<T: Float>(eps: T, up: [T; 3], a: &Fn1<T>, t: T) -> ([T; 3], [T; 3], [T; 3])
+    where f64: Cast<T>
+{
+    frames_at(eps, up, a, &[t])[0]
+}
+
+/// Computes rotation-minimizing frames of a curve at `n` evenly spaced inputs.
+///
+/// The inputs start at 0 and end at 1, using at least 2 samples.
+/// Each frame is the same as from `frame`, but the curve is only walked once,
+/// so the cost is proportional to `1 / eps` instead of `n / eps`.
+pub fn frames1<T: Float>(
+    n: usize,
+    eps: T,
+    up: [T; 3],
+    a: &Fn1<T>
+) -> Vec<([T; 3], [T; 3], [T; 3])>
+    where f64: Cast<T>
+{
+    let n = n.max(2);
+    let ts: Vec<T> = (0..n).map(|i| grid_param(i, n)).collect();
+    frames_at(eps, up, a, &ts)
+}
+
+/// Walks a rotation-minimizing frame along a curve, recording it at increasing inputs.
+fn frames_at<T: Float>(
+    eps: T,
+    up: [T; 3],
+    a: &Fn1<T>,
+    ts: &[T]
+) -> Vec<([T; 3], [T; 3], [T; 3])>
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let _2: T = 2.0.cast();
+    let max_steps: usize = 1_000_000;
+    let tangent_at = |s: T, prev: [T; 3]| normalize3(diff1(eps, a, s)).unwrap_or(prev);
+    let mut tangent = tangent_at(_0, [_0, _0, _1]);
+    let mut normal = normalize3(cross3(up, tangent))
+        .or_else(|| normalize3(cross3([_1, _0, _0], tangent)))
+        .or_else(|| normalize3(cross3([_0, _1, _0], tangent)))
+        .unwrap();
+    let reflect = |v: [T; 3], n: [T; 3]| {
+        let c = dot3(n, n);
+        if c == _0 {v} else {sub3(v, scale3(n, _2 * dot3(n, v) / c))}
+    };
+
+    let mut pos = a(_0);
+    let mut prev = _0;
+    let mut steps = 0;
+    let mut frames = Vec::with_capacity(ts.len());
+    for &t in ts {
+        let t = t.max(prev).min(_1);
+        // Steps of at most `eps`, or a single step once the step budget is spent.
+        let budget = max_steps.saturating_sub(steps).max(1);
+        let count = if eps > _0 {(t - prev) / eps} else {_0};
+        // Rounds `count` up by search, since `T` can not be converted to an integer.
+        let n = last_where(budget, |k| (k as f64).cast() < count) + 1;
+        steps += n;
+        for i in 1..n + 1 {
+            let s = prev + (t - prev) * (i as f64 / n as f64).cast();
+            let next_pos = a(s);
+            let next_tangent = tangent_at(s, tangent);
+            let v1 = sub3(next_pos, pos);
+            let normal_l = reflect(normal, v1);
+            let tangent_l = reflect(tangent, v1);
+            normal = reflect(normal_l, sub3(next_tangent, tangent_l));
+            tangent = next_tangent;
+            pos = next_pos;
+        }
+        prev = t;
+        // Removes drift from rounding errors.
+        normal = normalize3(sub3(normal, scale3(tangent, dot3(normal, tangent))))
+            .unwrap_or(normal);
+        frames.push((tangent, normal, cross3(tangent, normal)));
+    }
+    frames
+}
+
+/// Offsets a curve by a distance, parallel to the curve.
+///
+/// Each point moves by `dist` along the cross product of the unit tangent and `up`,
+/// which is to the right of the curve when looking down along `up`.
+/// A negative distance offsets to the left.
+/// The tangent is computed with finite differences with step `eps`.
+/// Where the tangent is zero or parallel to `up`, e.g. at cusps, the point is not offset.
+///
+/// Like any parallel curve, the offset curve gets cusps and loops where the radius of
+/// curvature on the offset side is smaller than the distance.
+pub fn offset_curve<T: Float>(eps: T, dist: T, up: [T; 3], a: Fn1<T>) -> Fn1<T> {
+    return Arc::new(move |t| {
+        let pos = a(t);
+        let side = normalize3(diff1(eps, &a, t)).and_then(|tangent| normalize3(cross3(tangent, up)));
+        match side {
+            Some(side) => add3(pos, scale3(side, dist)),
+            None => pos,
+        }
+    })
+}
+
+/// Finds the input of a curve where it is a distance away from a point,
+/// searching from the start, or from the end if `from_end` is `true`.
+///
+/// Returns the other end if the curve never gets that far away.
+fn input_at_dist<T: Float>(a: &Fn1<T>, p: [T; 3], d: T, from_end: bool) -> T
+    where f64: Cast<T>
+{
+    let _1: T = One::one();
+    let _2: T = 2.0.cast();
+    let n = 64;
+    let param = |i: usize| -> T {
+        let s: T = (i as f64 / n as f64).cast();
+        if from_end {_1 - s} else {s}
+    };
+    let dist = |t: T| len3(sub3(a(t), p));
+    for i in 1..n + 1 {
+        if dist(param(i)) < d {continue}
+        let (mut near, mut far) = (param(i - 1), param(i));
+        for _ in 0..50 {
+            let mid = (near + far) / _2;
+            if dist(mid) < d {near = mid} else {far = mid}
+        }
+        return (near + far) / _2;
+    }
+    param(n)
+}
+
+/// Joins two curves with a circular arc of a radius.
+///
+/// The end of `a` should be at the start of `b`.
+/// Both curves are trimmed where they are at the tangent distance from the corner,
+/// and an arc tangent to the directions of the curves at the corner is inserted,
+/// so the arc is exactly tangent when the curves are straight near the corner.
+/// The result goes through `a`, the arc and `b`,
+/// with input proportional to the approximate length of each part.
+///
+/// When the tangent distance is longer than the distance from the corner to the far end
+/// of either curve, the radius is reduced such that the shorter curve is consumed entirely.
+/// When the curves continue straight or turn back at the corner, no arc is inserted.
+pub fn fillet<T: Float>(radius: T, a: Fn1<T>, b: Fn1<T>) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let eps: T = 0.001.cast();
+    let (a0, corner, b1) = (a(_0), a(_1), b(_1));
+    let dirs = (
+        normalize3(scale3(diff1(eps, &a, _1), -_1)),
+        normalize3(diff1(eps, &b, _0))
+    );
+    let (u, v) = match dirs {
+        (Some(u), Some(v)) => (u, v),
+        _ => return conn(vec![(arc_length(64, &a), a), (arc_length(64, &b), b)]),
+    };
+    // The angle at the corner, between the directions back along `a` and forward along `b`.
+    let sin = len3(cross3(u, v));
+    let cos = dot3(u, v);
+    if radius <= _0 || sin < 0.000001.cast() {
+        return conn(vec![(arc_length(64, &a), a), (arc_length(64, &b), b)]);
+    }
+    let angle = sin.atan2(cos);
+    let half = angle / 2.0.cast();
+    let max_dist = len3(sub3(a0, corner)).min(len3(sub3(b1, corner)));
+    let dist = (radius / half.tan()).min(max_dist);
+    let radius = dist * half.tan();
+
+    let ta = input_at_dist(&a, corner, dist, true);
+    let tb = input_at_dist(&b, corner, dist, false);
+    let (pa, pb) = (add3(corner, scale3(u, dist)), add3(corner, scale3(v, dist)));
+    let (ea, eb) = (sub3(a(ta), pa), sub3(b(tb), pb));
+    let center = add3(corner, scale3(normalize3(add3(u, v)).unwrap(), radius / half.sin()));
+    let (ca, cb) = (sub3(pa, center), sub3(pb, center));
+    let pi: T = PI.cast();
+    let sweep = pi - angle;
+    let arc: Fn1<T> = Arc::new(move |s| {
+        let w0 = ((_1 - s) * sweep).sin() / sweep.sin();
+        let w1 = (s * sweep).sin() / sweep.sin();
+        // Moves the ends of the arc onto the trimmed curves, in case they are not straight.
+        add3(
+            add3(center, add3(scale3(ca, w0), scale3(cb, w1))),
+            add3(scale3(ea, _1 - s), scale3(eb, s))
+        )
+    });
+    let a = seg1([_0, ta], a);
+    let b = seg1([tb, _1], b);
+    conn(vec![
+        (arc_length(64, &a), a),
+        (radius * sweep, arc),
+        (arc_length(64, &b), b),
+    ])
+}
+
+/// Repeats a curved cube side by side along the x axis.
+///
+/// The x-input is split into `count` equal slices, each replaying the whole curved cube,
+/// offset along the x axis by the width of its bounding box times the slice index.
+/// The bounding box is computed like `bounds3`, with 9 samples in each direction,
+/// so copies of boxes abut exactly, while copies of curved shapes might overlap slightly.
+/// A count of 1 or less returns the curved cube unchanged.
+pub fn tilex3<T: Float>(count: usize, a: Fn3<T>) -> Fn3<T>
+    where f64: Cast<T>
+{
+    if count <= 1 {return a}
+    let (min, max) = bounds3(9, 9, 9, &a);
+    let width = max[0] - min[0];
+    return Arc::new(move |t| {
+        let (i, x) = split(t[0], count);
+        let pos = a([x, t[1], t[2]]);
+        let dx: T = width * (i as f64).cast();
+        [pos[0] + dx, pos[1], pos[2]]
+    })
+}
+
+/// The parameters of the Gielis superformula, see `superformula`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SuperParams<T> {
+    /// The rotational symmetry, as the number of lobes.
+    pub m: T,
+    /// The overall exponent.
+    pub n1: T,
+    /// The exponent of the cosine term.
+    pub n2: T,
+    /// The exponent of the sine term.
+    pub n3: T,
+    /// The scale of the cosine term.
+    pub a: T,
+    /// The scale of the sine term.
+    pub b: T,
+}
+
+/// Creates a curve from the Gielis superformula, located at a center.
+///
+/// The distance from the center is
+/// `r(θ) = (|cos(m θ / 4) / a|^n2 + |sin(m θ / 4) / b|^n3)^(-1 / n1)`.
+/// For example, `m = 4` with all exponents 2 and `a = b = 1` is the unit circle,
+/// and `m = 0` with `a = 1` and `n3 > 0` is the unit circle for any other exponents.
+///
+/// The input is the angle starting at 0, rotating 360 degrees around the center,
+/// which closes the curve when `m` is an integer.
+/// The curve is flat along the z axis.
+/// Where the distance is not finite, e.g. when both terms are zero, the point is at the center.
+pub fn superformula<T: Float>(center: [T; 3], params: SuperParams<T>) -> Fn1<T>
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let two_pi: T = TAU.cast();
+    let _4: T = 4.0.cast();
+    let inf = _1 / _0;
+    let SuperParams {m, n1, n2, n3, a, b} = params;
+    // Taking the absolute value with `signum` keeps zero at zero, such that `0^n` is defined.
+    let abs = |x: T| x * x.signum();
+    return Arc::new(move |t| {
+        let angle = t * two_pi;
+        let phi = m * angle / _4;
+        let sum = abs(phi.cos() / a).powf(n2) + abs(phi.sin() / b).powf(n3);
+        let r = sum.powf(-_1 / n1);
+        // The distance is never negative, and comparisons with NaN are false.
+        let r = if r < inf {r} else {_0};
+        [center[0] + r * angle.cos(), center[1] + r * angle.sin(), center[2]]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(crate) fn unit_sphere_shell() -> Fn2<f64> {
+        let s = sphere([0.0, 0.0, 0.0], 1.0);
+        Arc::new(move |t| s([t[0], t[1], 1.0]))
+    }
+
+    pub(crate) fn flat() -> Fn2<f64> {
+        Arc::new(|t: [f64; 2]| [t[0], t[1], 0.0])
+    }
+
+    #[test]
+    fn catenary_passes_through_ends_and_is_symmetric() {
+        let (a, b) = ([0.0, 0.0, 1.0], [4.0, 2.0, 1.0]);
+        let c = catenary(a, b, 1.5);
+        assert_eq!(c(0.0), a);
+        assert_eq!(c(1.0), b);
+        for i in 1..10 {
+            let t = i as f64 / 10.0;
+            let (p, q) = (c(t), c(1.0 - t));
+            assert!((p[2] - q[2]).abs() < 1e-9);
+            assert!(p[2] < 1.0);
+        }
+        assert!((arc_length(1000, &c) - (len3(sub3(b, a)) + 1.5)).abs() < 1e-3);
+        assert_eq!(catenary(a, b, f64::NAN)(0.5), lin(a, b)(0.5));
+    }
+
+    #[test]
+    fn cquad_center_without_smoothing_is_finite() {
+        let ab = lin([0.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        let cd = lin([1.0, 0.0, 0.0], [1.0, 1.0, 0.0]);
+        let ac = lin([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        let bd = lin([0.0, 1.0, 0.0], [1.0, 1.0, 0.0]);
+        let p: [f64; 3] = cquad(0.0, ab, cd, ac, bd)([0.5, 0.5]);
+        assert!(p.iter().all(|x| x.is_finite()));
+        assert_eq!(p, [0.5, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn linn_is_weighted_mean() {
+        let a = lin([0.0, 0.0, 0.0], [3.0, 0.0, 0.0]);
+        let b = lin([3.0, 0.0, 0.0], [3.0, 6.0, 0.0]);
+        let c = lin([0.0, 3.0, 0.0], [0.0, 0.0, 9.0]);
+        let mean = linn(vec![(1.0, a.clone()), (1.0, b.clone()), (1.0, c.clone())]);
+        let scaled = linn(vec![(2.0, a.clone()), (2.0, b), (2.0, c)]);
+        for &t in &[0.0, 0.25, 1.0] {
+            let (pa, pb, pc) = (a(t), [3.0, 6.0 * t, 0.0], [0.0, 3.0 - 3.0 * t, 9.0 * t]);
+            let expected = scale3(add3(add3(pa, pb), pc), 1.0 / 3.0);
+            assert!(len3(sub3(mean(t), expected)) < 1e-12);
+            assert!(len3(sub3(scaled(t), expected)) < 1e-12);
+        }
+        assert_eq!(linn(vec![(0.0, a.clone())])(1.0), [0.0; 3]);
+        assert_eq!(linn(vec![(0.5, a)])(1.0), [3.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn margin_at_half_is_finite_and_clamped() {
+        let a = lin([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        for &m in &[-0.5, -1.0] {
+            let f = margin1(m, a.clone());
+            let (p0, p1): ([f64; 3], [f64; 3]) = (f(0.0), f(1.0));
+            assert!(p0.iter().chain(&p1).all(|x| x.is_finite()));
+            assert!(p0[0] < -1000.0 && p1[0] > 1000.0);
+        }
+        assert_eq!(margin1(-1.0, a.clone())(0.5), margin1(-0.5, a)(0.5));
+    }
+
+    #[test]
+    fn axis_basis_is_right_handed_and_orthonormal() {
+        let axes = [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [0.0, -1.0, 1e-9], [1.0, 2.0, -0.5], [0.0; 3]];
+        for &axis in &axes {
+            let (u, v) = axis_basis::<f64>(axis);
+            let n = normalize3(axis).unwrap_or([0.0, 0.0, 1.0]);
+            assert!((len3(u) - 1.0).abs() < 1e-12 && (len3(v) - 1.0).abs() < 1e-12);
+            assert!(dot3(u, v).abs() < 1e-12);
+            assert!(len3(sub3(cross3(u, v), n)) < 1e-12);
+        }
+        assert_eq!(axis_basis::<f64>([0.0, 0.0, 2.0]), ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]));
+    }
+
+    #[test]
+    fn frame_is_continuous_along_arc() {
+        let a = arc([1.0f64, 2.0, 3.0], 2.0, 0.0, 3.0);
+        let frames = frames1(101, 0.001, [0.0, 0.0, 1.0], &a);
+        for w in frames.windows(2) {
+            let ((t0, n0, b0), (t1, n1, b1)) = (w[0], w[1]);
+            assert!(len3(sub3(t1, t0)) < 0.05);
+            assert!(len3(sub3(n1, n0)) < 0.05);
+            assert!(len3(sub3(b1, b0)) < 1e-6);
+            assert!(dot3(t1, n1).abs() < 1e-9);
+        }
+        for &(i, t) in &[(0, 0.0), (37, 0.37), (100, 1.0)] {
+            let (tangent, normal, binormal) = frame(0.001, [0.0, 0.0, 1.0], &a, t);
+            assert!(len3(sub3(tangent, frames[i].0)) < 1e-6);
+            assert!(len3(sub3(normal, frames[i].1)) < 1e-6);
+            assert!(len3(sub3(binormal, frames[i].2)) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn superformula_reduces_to_circle() {
+        let circle = |n2: f64, n3: f64, m: f64| superformula([1.0, 2.0, 3.0], SuperParams {
+            m, n1: 2.0, n2, n3, a: 1.0, b: 1.0
+        });
+        for f in &[circle(2.0, 2.0, 4.0), circle(5.0, 0.5, 0.0), circle(-1.0, 3.0, 0.0)] {
+            for i in 0..=100 {
+                let p = f(i as f64 / 100.0);
+                assert!((len3(sub3(p, [1.0, 2.0, 3.0])) - 1.0).abs() < 1e-12);
+                assert_eq!(p[2], 3.0);
+            }
+        }
+    }
+
+    fn near(a: [f64; 3], b: [f64; 3]) -> bool {
+        len3(sub3(a, b)) < 1e-9
+    }
+
+    #[test]
+    fn cylinder_ends_lie_on_planes() {
+        let c = cylinder([1.0, 2.0, 3.0], 2.0, 4.0);
+        for i in 0..8 {
+            let t = [i as f64 / 8.0, 0.7];
+            let (bottom, top) = (c([t[0], t[1], 0.0]), c([t[0], t[1], 1.0]));
+            assert_eq!((bottom[2], top[2]), (1.0, 5.0));
+            assert!((len3(sub3(top, [1.0, 2.0, 5.0])) - 1.4).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn cone_silhouette_has_constant_slope() {
+        let c = cone([0.0, 0.0, 4.0], [0.0, 0.0, 0.0], 2.0);
+        for i in 0..=10 {
+            let s = i as f64 / 10.0;
+            let p = c([0.3, 1.0, s]);
+            let radius = (p[0] * p[0] + p[1] * p[1]).sqrt();
+            assert!((radius - 2.0 * s).abs() < 1e-12);
+            assert!((p[2] - 4.0 * (1.0 - s)).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn helix_height_and_radius() {
+        let h = helix([1.0, 2.0, 3.0], 2.0, 0.5, 3.0);
+        assert!(near(sub3(h(1.0), h(0.0)), [0.0, 0.0, 1.5]));
+        for i in 0..=30 {
+            let p = h(i as f64 / 30.0);
+            assert!(((p[0] - 1.0).hypot(p[1] - 2.0) - 2.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn full_arc_matches_circle() {
+        let a = arc([1.0, 2.0, 3.0], 2.0, 0.0, TAU);
+        let c = y2(1.0, circle([1.0, 2.0, 3.0], 2.0));
+        for i in 0..=16 {
+            let t = i as f64 / 16.0;
+            assert!(near(a(t), c(t)));
+        }
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_points() {
+        let points = [[0.0, 0.0, 0.0], [1.0, 2.0, 0.0], [3.0, 1.0, 1.0], [4.0, 4.0, 0.0], [6.0, 0.0, 2.0]];
+        for f in &[catmull_rom(&points), catmull_rom_centripetal(&points)] {
+            for (i, &p) in points.iter().enumerate() {
+                assert!(near(f(i as f64 / 4.0), p));
+            }
+        }
+    }
+
+    #[test]
+    fn tangent_of_line_is_constant() {
+        let (a, b) = ([1.0, 2.0, 3.0], [4.0, -2.0, 3.0]);
+        let tangent = tangent1(0.001, lin(a, b));
+        for i in 0..=10 {
+            assert!(near(tangent(i as f64 / 10.0), [0.6, -0.8, 0.0]));
+        }
+    }
+
+    #[test]
+    fn normal_of_flat_surface_is_constant() {
+        let flat = ext1(lin([0.0, 0.0, 0.0], [2.0, 0.0, 0.0]), lin([0.0, 0.0, 0.0], [0.0, 3.0, 0.0]));
+        let normal = normal2(0.001, flat);
+        for i in 0..=4 {
+            for j in 0..=4 {
+                let n = normal([i as f64 / 4.0, j as f64 / 4.0]);
+                assert!(near(n, [0.0, 0.0, 1.0]) || near(n, [0.0, 0.0, -1.0]));
+                assert!(near(n, normal([0.5, 0.5])));
+            }
+        }
+    }
+
+    #[test]
+    fn reparam_by_length_reaches_midpoint() {
+        let line = reparam_by_length(100, lin([0.0, 0.0, 0.0], [2.0, 4.0, 0.0]));
+        assert!(near(line(0.25), [0.5, 1.0, 0.0]));
+        // Control points bunched at the start make the input very nonuniform.
+        let curve = cbez([0.0f64, 0.0, 0.0], [0.1, 0.0, 0.0], [0.2, 0.0, 0.0], [10.0, 0.0, 0.0]);
+        assert!((curve(0.5)[0] - 5.0).abs() > 1.0);
+        let even = reparam_by_length(1000, curve);
+        assert!((even(0.5)[0] - 5.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn rot_quarter_turn_around_z() {
+        let a = rot([0.0, 0.0, 2.0], PI / 2.0, lin([1.0, 0.0, 1.0], [1.0, 2.0, 1.0]));
+        assert!(near(a(0.0), [0.0, 1.0, 1.0]));
+        assert!(near(a(1.0), [-2.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn scl_around_pivot() {
+        let a = scl([1.0, 1.0, 1.0], [2.0, 3.0, -1.0], lin([1.0, 1.0, 1.0], [2.0, 2.0, 2.0]));
+        assert_eq!(a(0.0), [1.0, 1.0, 1.0]);
+        assert_eq!(a(1.0), [3.0, 4.0, 0.0]);
+    }
+
+    #[test]
+    fn affine_translation_matches_off() {
+        let m = [
+            [1.0, 0.0, 0.0, 2.0],
+            [0.0, 1.0, 0.0, -3.0],
+            [0.0, 0.0, 1.0, 0.5],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        let a = cbez([0.0, 0.0, 0.0], [1.0, 2.0, 0.0], [3.0, 1.0, 1.0], [4.0, 0.0, 2.0]);
+        let (b, c) = (affine(m, a.clone()), off([2.0, -3.0, 0.5], a));
+        for i in 0..=10 {
+            let t = i as f64 / 10.0;
+            assert!(near(b(t), c(t)));
+        }
+    }
+
+    #[test]
+    fn seg2_crops_center_quarter() {
+        let a: Fn2<f64> = Arc::new(|t| [t[0] * 4.0, t[1] * 2.0, t[0] * t[1]]);
+        let b = seg2([0.25, 0.75], [0.25, 0.75], a.clone());
+        assert!(near(b([0.0, 0.0]), a([0.25, 0.25])));
+        assert!(near(b([1.0, 1.0]), a([0.75, 0.75])));
+        assert!(near(b([0.5, 0.5]), [2.0, 1.0, 0.25]));
+    }
+
+    #[test]
+    fn seg3_crops_identity_cube() {
+        let id = boxed([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]);
+        let b = seg3([0.2, 0.4], [0.5, 1.0], [1.0, 0.0], id);
+        assert!(near(b([0.0, 0.0, 0.0]), [0.2, 0.5, 1.0]));
+        assert!(near(b([1.0, 1.0, 1.0]), [0.4, 1.0, 0.0]));
+        assert!(near(b([1.0, 0.0, 0.5]), [0.4, 0.5, 0.5]));
+    }
+
+    #[test]
+    fn reverse_twice_round_trips() {
+        let a: Fn2<f64> = Arc::new(|t| [t[0] * 3.0, t[1] * t[1], t[0] - t[1]]);
+        let b: Fn3<f64> = Arc::new(|t| [t[0] * 3.0, t[1] * t[2], t[2] - t[0]]);
+        let (a2, a3) = (revu2(revu2(a.clone())), revv2(revv2(a.clone())));
+        let (bx, by, bz) = (revx3(revx3(b.clone())), revy3(revy3(b.clone())), revz3(revz3(b.clone())));
+        for &(u, v, w) in &[(0.0, 0.0, 0.0), (0.2, 0.7, 0.4), (1.0, 0.5, 1.0)] {
+            assert!(near(a2([u, v]), a([u, v])) && near(a3([u, v]), a([u, v])));
+            assert!(near(bx([u, v, w]), b([u, v, w])));
+            assert!(near(by([u, v, w]), b([u, v, w])));
+            assert!(near(bz([u, v, w]), b([u, v, w])));
+        }
+        assert!(near(revu2(a.clone())([0.0, 0.5]), a([1.0, 0.5])));
+    }
+
+    #[test]
+    fn swap2_and_swizzle3_permute_inputs() {
+        let a: Fn2<f64> = Arc::new(|t| [t[0], 2.0 * t[1], 0.0]);
+        assert_eq!(swap2(a)([0.25, 0.5]), [0.5, 0.5, 0.0]);
+        let b: Fn3<f64> = Arc::new(|t| t);
+        assert_eq!(swizzle3([2, 0, 1], b.clone())([1.0, 2.0, 3.0]), [3.0, 1.0, 2.0]);
+        assert_eq!(swizzle3([0, 1, 2], b)([1.0, 2.0, 3.0]), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn swizzle3_rejects_non_permutation() {
+        swizzle3([0, 0, 1], boxed([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn con_at_weights_zero_and_one_selects_one_side() {
+        let a = lin([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        let b = lin([0.0, 2.0, 0.0], [0.0, 3.0, 0.0]);
+        for &t in &[0.0f64, 0.5, 1.0] {
+            let p0 = con(0.0, a.clone(), b.clone())(t);
+            let p1 = con(1.0, a.clone(), b.clone())(t);
+            assert!(p0.iter().chain(&p1).all(|x| x.is_finite()));
+            assert_eq!(p0, b(t));
+            assert_eq!(p1, a(t));
+        }
+    }
+
+    #[test]
+    fn con_axis_at_weights_zero_and_one_selects_one_side() {
+        let a2: Fn2<f64> = Arc::new(|t| [t[0], t[1], 1.0]);
+        let b2: Fn2<f64> = Arc::new(|t| [t[0], t[1], 2.0]);
+        let a3: Fn3<f64> = Arc::new(|t| [t[0], t[1], t[2] + 1.0]);
+        let b3: Fn3<f64> = Arc::new(|t| [t[0], t[1], t[2] + 2.0]);
+        type Con2 = fn(f64, Fn2<f64>, Fn2<f64>) -> Fn2<f64>;
+        type Con3 = fn(f64, Fn3<f64>, Fn3<f64>) -> Fn3<f64>;
+        let cons2: [Con2; 2] = [conx2, cony2];
+        let cons3: [Con3; 3] = [conx3, cony3, conz3];
+        for &t in &[0.0, 0.5, 1.0] {
+            for f in &cons2 {
+                assert_eq!(f(0.0, a2.clone(), b2.clone())([t, t]), b2([t, t]));
+                assert_eq!(f(1.0, a2.clone(), b2.clone())([t, t]), a2([t, t]));
+            }
+            for f in &cons3 {
+                assert_eq!(f(0.0, a3.clone(), b3.clone())([t, t, t]), b3([t, t, t]));
+                assert_eq!(f(1.0, a3.clone(), b3.clone())([t, t, t]), a3([t, t, t]));
+            }
+        }
+    }
+
+    #[test]
+    fn conn_breakpoints_follow_weights() {
+        let pts = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 0.0]];
+        let pieces = (0..4).map(|i| (1.0, lin(pts[i], pts[i + 1]))).collect();
+        let f = conn(pieces);
+        for (i, p) in pts.iter().enumerate() {
+            assert!(near(f(i as f64 / 4.0), *p));
+        }
+        assert!(near(f(0.125), [0.5, 0.0, 0.0]));
+        let g = conn(vec![(3.0, lin(pts[0], pts[1])), (0.0, lin(pts[3], pts[4])), (1.0, lin(pts[1], pts[2]))]);
+        assert!(near(g(0.75), pts[1]));
+        assert!(near(g(0.875), [1.0, 0.5, 0.0]));
+        assert_eq!(conn::<f64>(vec![])(0.5), [0.0; 3]);
+    }
+
+    #[test]
+    fn smooth2_matches_ends_and_is_flat_near_them() {
+        let a = lin([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]);
+        let b = lin([1.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+        let (s, l) = (smooth2(a.clone(), b.clone()), lin2(a.clone(), b.clone()));
+        assert_eq!(s(0.0), a(0.0));
+        assert_eq!(s(1.0), b(1.0));
+        let h = 1e-3f64;
+        for &t in &[0.0, 1.0 - h] {
+            let ds = (s(t + h)[0] - s(t)[0]) / h;
+            let dl = (l(t + h)[0] - l(t)[0]) / h;
+            assert!(ds < 0.01 * dl);
+        }
+    }
+
+    #[test]
+    fn cache1_is_close_to_direct_evaluation() {
+        let a = trefoil(1.0);
+        for &n in &[16, 64, 256] {
+            let c = cache1(n, a.clone());
+            assert_eq!(c(0.0), a(0.0));
+            assert_eq!(c(1.0), a(1.0));
+            // The curvature of the trefoil is bounded, so the error falls with the square of n.
+            let tol = 400.0 / (n * n) as f64;
+            for i in 0..=100 {
+                let t = i as f64 / 100.0;
+                assert!(len3(sub3(c(t), a(t))) < tol);
+            }
+        }
+    }
+
+    #[test]
+    fn bounds3_of_sphere() {
+        let (min, max) = bounds3(5, 5, 2, &sphere([1.0, 2.0, 3.0], 2.0));
+        assert!(near(min, [-1.0, 0.0, 1.0]));
+        assert!(near(max, [3.0, 4.0, 5.0]));
+    }
+
+    #[test]
+    fn sweep_circle_along_lin_is_cylinder() {
+        let c = circle([0.0, 0.0, 0.0], 0.5);
+        let profile: Fn1<f64> = Arc::new(move |t| c([t, 1.0]));
+        let s = sweep(1e-3, lin([0.0, 0.0, 0.0], [5.0, 0.0, 0.0]), profile);
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let (u, v) = (i as f64 / 10.0, j as f64 / 10.0);
+                let p = s([u, v]);
+                assert!((p[0] - 5.0 * u).abs() < 1e-9);
+                assert!(((p[1] * p[1] + p[2] * p[2]).sqrt() - 0.5).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn revolve_semicircle_is_sphere() {
+        let profile: Fn1<f64> = Arc::new(|t| {
+            let angle = t * PI;
+            [2.0 * angle.sin(), 0.0, 2.0 * angle.cos()]
+        });
+        let s = revolve(profile);
+        for i in 0..=8 {
+            for j in 0..=8 {
+                let p = s([i as f64 / 8.0, j as f64 / 8.0]);
+                assert!((len3(p) - 2.0).abs() < 1e-12);
+            }
+        }
+        for j in 0..=8 {
+            let v = j as f64 / 8.0;
+            assert_eq!(s([0.0, v]), s([1.0, v]));
+        }
+    }
+
+    #[test]
+    fn loft_between_offset_circles() {
+        let ring = |z: f64, r: f64| -> Fn1<f64> {
+            let c = circle([0.0, 0.0, z], r);
+            Arc::new(move |t| c([t, 1.0]))
+        };
+        let (a, b, c) = (ring(0.0, 1.0), ring(1.0, 2.0), ring(3.0, 1.5));
+        let l = loft(vec![a.clone(), b.clone(), c.clone()]);
+        for i in 0..=8 {
+            let u = i as f64 / 8.0;
+            assert!(near(l([u, 0.0]), a(u)));
+            assert!(near(l([u, 0.5]), b(u)));
+            assert!(near(l([u, 1.0]), c(u)));
+            let p = l([u, 0.25]);
+            assert!(p[2] > 0.0 && p[2] < 1.0);
+            assert!(((p[0] * p[0] + p[1] * p[1]).sqrt() - 1.5).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn superellipse_with_n_two_is_circle() {
+        let s = superellipse([1.0, 2.0, 3.0], 2.0, 2.0, 2.0);
+        let c = circle([1.0, 2.0, 3.0], 2.0);
+        for i in 0..=16 {
+            let t = i as f64 / 16.0;
+            assert!(near(s(t), c([t, 1.0])));
+        }
+    }
+
+    #[test]
+    fn lissajous_with_quarter_phase_is_circle() {
+        let l = lissajous([1.0, 2.0, 3.0], [2.0, 2.0, 2.0], [1.0, 1.0, 0.0], [0.0, PI / 2.0, 0.0]);
+        for i in 0..=16 {
+            let t = i as f64 / 16.0;
+            let p = sub3(l(t), [1.0, 2.0, 3.0]);
+            assert!((len3(p) - 2.0).abs() < 1e-12);
+            assert!(p[2].abs() < 1e-12);
+        }
+        assert!(near(l(0.0), l(1.0)));
+        assert!(near(l(0.25), [3.0, 2.0, 3.0]));
+    }
+
+    #[test]
+    fn trefoil_is_closed_and_bounded() {
+        let k = trefoil(0.5);
+        assert!(near(k(0.0), k(1.0)));
+        for i in 0..=1000 {
+            assert!(len3(k(i as f64 / 1000.0)) <= 1.5 + 1e-12);
+        }
+    }
+
+    #[test]
+    fn mobius_closes_onto_itself_flipped() {
+        let m = mobius(2.0, 1.0);
+        for j in 0..=8 {
+            let v = j as f64 / 8.0;
+            assert!(near(m([1.0, v]), m([0.0, 1.0 - v])));
+        }
+        assert!(!near(m([1.0, 0.0]), m([0.0, 0.0])));
+    }
+
+    #[test]
+    fn nearest_t1_recovers_input_on_cbez() {
+        let c = cbez([0.0, 0.0, 0.0], [1.0, 2.0, 0.0], [3.0, 2.0, 1.0], [4.0, 0.0, 0.0]);
+        for &t in &[0.0f64, 0.3, 0.77, 1.0] {
+            let found = nearest_t1(32, c(t), &c);
+            assert!((found - t).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn twist_rotates_top_of_cylinder() {
+        let c = twist(PI / 4.0, cylinder([0.0, 0.0, 1.0], 1.0, 2.0));
+        assert!(near(c([0.0, 1.0, 0.0]), [1.0, 0.0, 0.0]));
+        assert!(near(c([0.0, 1.0, 1.0]), [0.0, 1.0, 2.0]));
+        assert!(near(c([0.25, 1.0, 1.0]), [-1.0, 0.0, 2.0]));
+    }
+
+    #[test]
+    fn bend_puts_cylinder_axis_on_arc() {
+        let c = cylinder([0.0, 0.0, 0.0], 0.1, 2.0);
+        let lying: Fn3<f64> = Arc::new(move |t| {
+            let p = c(t);
+            [p[2] + 1.0, p[0], p[1]]
+        });
+        let radius = 4.0 / PI;
+        let b = bend(radius, lying);
+        assert!(near(b([0.0, 0.0, 0.0]), [0.0, 0.0, 0.0]));
+        assert!(near(b([0.0, 0.0, 1.0]), [radius, radius, 0.0]));
+        for i in 0..=8 {
+            let p = b([0.0, 0.0, i as f64 / 8.0]);
+            assert!((len3(sub3(p, [0.0, radius, 0.0])) - radius).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn taper_turns_cylinder_into_cone() {
+        let c = taper(1.0, 0.0, [0.0, 2.0], cylinder([0.0, 0.0, 1.0], 1.0, 2.0));
+        for i in 0..=8 {
+            for k in 0..=8 {
+                let h = k as f64 / 8.0;
+                let p = c([i as f64 / 8.0, 1.0, h]);
+                assert!(((p[0] * p[0] + p[1] * p[1]).sqrt() - (1.0 - h)).abs() < 1e-12);
+                assert!((p[2] - 2.0 * h).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn displace2_by_constant_offsets_plane() {
+        let a = plane([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        let d = displace2(1e-3, Arc::new(|_| 0.5), a.clone());
+        for i in 0..=4 {
+            for j in 0..=4 {
+                let t = [i as f64 / 4.0, j as f64 / 4.0];
+                assert!(near(d(t), add3(a(t), [0.0, 0.0, 0.5])));
+            }
+        }
+    }
+
+    #[test]
+    fn samples1_includes_end_points() {
+        let (a, b) = ([1.0, 2.0, 3.0], [4.0, 6.0, 3.0]);
+        let pts: Vec<[f64; 3]> = samples1(5, lin(a, b)).collect();
+        assert_eq!(pts.len(), 5);
+        assert_eq!(pts[0], a);
+        assert_eq!(pts[4], b);
+        assert_eq!(samples1(1, lin(a, b)).collect::<Vec<_>>(), vec![a]);
+        assert_eq!(samples1(0, lin(a, b)).count(), 0);
+    }
+
+    #[test]
+    fn flatten1_adapts_to_curvature() {
+        let (a, b) = ([0.0, 0.0, 0.0], [4.0, 0.0, 0.0]);
+        assert_eq!(flatten1(1e-6, &lin(a, b)), vec![a, b]);
+        let c = cbez(a, [1.0, 3.0, 0.0], [3.0, 3.0, 0.0], b);
+        let pts = flatten1(1e-3, &c);
+        assert!(pts.len() > 2);
+        assert_eq!(pts[0], a);
+        assert_eq!(pts[pts.len() - 1], b);
+    }
+
+    #[test]
+    fn close1_welds_circle_ends() {
+        let c = circle([1.0, 2.0, 0.0], 3.0);
+        let ring: Fn1<f64> = Arc::new(move |t| c([t, 1.0]));
+        let closed = close1(1e-9, ring.clone());
+        assert_eq!(closed(1.0), closed(0.0));
+        assert!(near(closed(0.5), ring(0.5)));
+        let open = close1(1e-9, lin([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]));
+        assert_eq!(open(1.0), [1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn edges_connect_corners() {
+        let a = cquad(0.0,
+            lin([0.0, 0.0, 0.0], [0.0, 1.0, 0.5]), lin([2.0, 0.0, 0.0], [2.0, 1.0, 1.0]),
+            lin([0.0, 0.0, 0.0], [2.0, 0.0, 0.0]), lin([0.0, 1.0, 0.5], [2.0, 1.0, 1.0]));
+        let c = corners2(&a);
+        let edges = [edge_bottom(a.clone()), edge_right(a.clone()), edge_top(a.clone()), edge_left(a)];
+        for (i, e) in edges.iter().enumerate() {
+            assert_eq!(e(0.0), c[i]);
+            assert_eq!(e(1.0), c[(i + 1) % 4]);
+        }
+    }
+
+    #[test]
+    fn corners3_of_unit_cube() {
+        let c = corners3(&boxed([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+        for (i, p) in c.iter().enumerate() {
+            assert_eq!(*p, [(i & 1) as f64, ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64]);
+        }
+    }
+
+    #[test]
+    fn faces3_corners_are_solid_corners() {
+        let (min, max) = ([0.0, 0.0, 0.0], [1.0, 2.0, 3.0]);
+        let a = boxed(min, max);
+        let solid = corners3(&a);
+        for (k, face) in faces3(a).iter().enumerate() {
+            let (axis, side) = (k / 2, if k % 2 == 0 {min} else {max});
+            let c = corners2(face);
+            for (i, p) in c.iter().enumerate() {
+                assert!(solid.contains(p));
+                assert_eq!(p[axis], side[axis]);
+                assert!(!c[i + 1..].contains(p));
+            }
+        }
+    }
+
+    #[test]
+    fn shell_of_plane_is_offset_by_thickness() {
+        let s = shell(1e-3, 0.25, plane([0.0, 0.0, 1.0], [2.0, 0.0, 0.0], [0.0, 3.0, 0.0]));
+        for i in 0..=4 {
+            for j in 0..=4 {
+                let (u, v) = (i as f64 / 4.0, j as f64 / 4.0);
+                assert!(near(sub3(s([u, v, 1.0]), s([u, v, 0.0])), [0.0, 0.0, 0.25]));
+                assert!(near(s([u, v, 0.5]), [2.0 * u, 3.0 * v, 1.125]));
+            }
+        }
+    }
+
+    #[test]
+    fn jacobian3_of_scl_is_diagonal() {
+        let a = scl([1.0, 1.0, 1.0], [2.0, 3.0, 0.5], boxed([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+        for &t in &[[0.0, 0.0, 0.0], [0.5, 0.25, 1.0]] {
+            let j = jacobian3(1e-4, &a, t);
+            for (i, &s) in [2.0, 3.0, 0.5].iter().enumerate() {
+                for (k, &x) in j[i].iter().enumerate() {
+                    let expected: f64 = if i == k {s} else {0.0};
+                    assert!((x - expected).abs() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn surface_area_of_plane_and_cylinder() {
+        let p = plane([1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 3.0, 1.0]);
+        assert!((surface_area(2, 2, &p) - 6.0 * 10.0f64.sqrt() / 3.0).abs() < 1e-12);
+        let c = cylinder([0.0, 0.0, 0.0], 1.0, 3.0);
+        let wall: Fn2<f64> = Arc::new(move |t| c([t[0], 1.0, t[1]]));
+        assert!((surface_area(32, 2, &wall) - 6.0 * PI).abs() < 0.1);
+    }
+
+    #[test]
+    fn volume3_of_box_and_sphere() {
+        let b = boxed([1.0, 0.0, 0.0], [2.0, 2.0, 3.0]);
+        assert!((volume3(2, 2, 2, &b) - 6.0f64).abs() < 1e-12);
+        let s = sphere([1.0, 2.0, 3.0], 1.0);
+        assert!((volume3(64, 64, 2, &s) - 4.0 / 3.0 * PI).abs() < 0.02);
+    }
+
+    #[test]
+    fn translate_is_curried_off() {
+        let p = [1.0, -2.0, 0.5];
+        let move_by = translate(p);
+        let curves = vec![lin([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]), trefoil(1.0), catenary([0.0, 0.0, 0.0], [2.0, 0.0, 0.0], 1.0)];
+        for c in curves {
+            let (a, b) = (move_by(c.clone()), off(p, c));
+            for i in 0..=4 {
+                let t = i as f64 / 4.0;
+                assert_eq!(a(t), b(t));
+            }
+        }
+    }
+
+    #[test]
+    fn mirror_plane_along_x_is_mx() {
+        let a = trefoil(1.0);
+        let (m, n) = (mirror_plane([1.5, 0.0, 0.0], [2.0, 0.0, 0.0], a.clone()), mx(1.5, a.clone()));
+        for i in 0..=8 {
+            let t = i as f64 / 8.0;
+            assert!(near(m(t), n(t)));
+        }
+        let b = mirror_plane([0.0, 0.0, 0.0], [0.0; 3], a.clone());
+        assert_eq!(b(0.3), a(0.3));
+    }
+
+    #[test]
+    fn rose_with_one_petal_is_circle() {
+        let r = rose([1.0, 2.0, 3.0], 2.0, 1.0, 1.0);
+        for i in 0..=16 {
+            let p = r(i as f64 / 16.0);
+            assert!((len3(sub3(p, [2.0, 2.0, 3.0])) - 1.0).abs() < 1e-12);
+        }
+        assert!(near(r(0.0), [3.0, 2.0, 3.0]));
+        assert!(near(r(0.25), [1.0, 2.0, 3.0]));
+    }
+
+    #[test]
+    fn project2_onto_plane() {
+        let p = plane([1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 4.0, 0.0]);
+        let (uv, pos) = project2(8, 8, [2.0, 3.0, 5.0], &p);
+        assert!((uv[0] - 0.5f64).abs() < 1e-9 && (uv[1] - 0.75f64).abs() < 1e-9);
+        assert!(near(pos, [2.0, 3.0, 0.0]));
+    }
+
+    #[test]
+    fn con_axis_matches_fixed_axis_functions() {
+        let a2: Fn2<f64> = Arc::new(|t| [t[0], t[1], 1.0]);
+        let b2: Fn2<f64> = Arc::new(|t| [t[1], t[0], 2.0]);
+        let a3: Fn3<f64> = Arc::new(|t| [t[0], t[1], t[2]]);
+        let b3: Fn3<f64> = Arc::new(|t| [t[2], t[0], t[1] + 2.0]);
+        let x2 = (con_axis(0, 0.3, a2.clone(), b2.clone()), conx2(0.3, a2.clone(), b2.clone()));
+        let y2 = (con_axis(1, 0.3, a2.clone(), b2.clone()), cony2(0.3, a2, b2));
+        let x3 = (con_axis(0, 0.3, a3.clone(), b3.clone()), conx3(0.3, a3.clone(), b3.clone()));
+        let y3 = (con_axis(1, 0.3, a3.clone(), b3.clone()), cony3(0.3, a3.clone(), b3.clone()));
+        let z3 = (con_axis(2, 0.3, a3.clone(), b3.clone()), conz3(0.3, a3, b3));
+        for i in 0..=10 {
+            for j in 0..=10 {
+                let (u, v) = (i as f64 / 10.0, j as f64 / 10.0);
+                assert_eq!(x2.0([u, v]), x2.1([u, v]));
+                assert_eq!(y2.0([u, v]), y2.1([u, v]));
+                for &t in &[[u, v, 0.5], [0.5, u, v], [v, 0.5, u]] {
+                    assert_eq!(x3.0(t), x3.1(t));
+                    assert_eq!(y3.0(t), y3.1(t));
+                    assert_eq!(z3.0(t), z3.1(t));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn offset_curve_of_lin_is_parallel() {
+        let c = offset_curve(1e-3, 0.5, [0.0, 0.0, 1.0], lin([0.0, 0.0, 0.0], [4.0, 0.0, 0.0]));
+        for i in 0..=8 {
+            let t = i as f64 / 8.0;
+            assert!(near(c(t), [4.0 * t, -0.5, 0.0]));
+        }
+        let d = offset_curve(1e-3, -0.5, [0.0, 0.0, 1.0], lin([0.0, 0.0, 0.0], [4.0, 0.0, 0.0]));
+        assert!(near(d(0.5), [2.0, 0.5, 0.0]));
+    }
+
+    #[test]
+    fn fillet_between_perpendicular_lins_is_tangent() {
+        let a = lin([0.0, 0.0, 0.0], [2.0, 0.0, 0.0]);
+        let b = lin([2.0, 0.0, 0.0], [2.0, 2.0, 0.0]);
+        let f = fillet(0.5, a, b);
+        assert!(near(f(0.0), [0.0, 0.0, 0.0]));
+        assert!(near(f(1.0), [2.0, 2.0, 0.0]));
+        let n = 2000;
+        let pts: Vec<[f64; 3]> = (0..=n).map(|i| f(i as f64 / n as f64)).collect();
+        for p in &pts {
+            let on_a = p[1].abs() < 1e-9 && p[0] <= 1.5 + 1e-9;
+            let on_b = (p[0] - 2.0).abs() < 1e-9 && p[1] >= 0.5 - 1e-9;
+            let on_arc = (len3(sub3(*p, [1.5, 0.5, 0.0])) - 0.5).abs() < 1e-9;
+            assert!(on_a || on_b || on_arc);
+        }
+        // The direction turns gradually, without a kink where the arc meets the lines.
+        for w in pts.windows(3) {
+            let (d0, d1) = (sub3(w[1], w[0]), sub3(w[2], w[1]));
+            assert!(dot3(d0, d1) / (len3(d0) * len3(d1)) > 0.999);
+        }
+    }
+
+    #[test]
+    fn map_out_can_express_off() {
+        let pos = [1.0, 2.0, -3.0];
+        let a = trefoil(1.0);
+        let (b, c) = (map_out(Arc::new(move |p| add3(p, pos)), a.clone()), off(pos, a));
+        for i in 0..=8 {
+            let t = i as f64 / 8.0;
+            assert_eq!(b(t), c(t));
+        }
+    }
+
+    #[test]
+    fn map_in_can_express_swap2() {
+        let a: Fn2<f64> = Arc::new(|t| [t[0], 2.0 * t[1], t[0] * t[1]]);
+        let (b, c) = (map_in2(Arc::new(|t| [t[1], t[0]]), a.clone()), swap2(a));
+        for i in 0..=4 {
+            for j in 0..=4 {
+                let t = [i as f64 / 4.0, j as f64 / 4.0];
+                assert_eq!(b(t), c(t));
+            }
+        }
+        let r = map_in1(Arc::new(|t| 1.0 - t), trefoil(1.0));
+        assert_eq!(r(0.25), rev(trefoil(1.0))(0.25));
+        let s = sphere([0.0, 0.0, 0.0], 1.0);
+        assert_eq!(map_in3(Arc::new(|t| t), s.clone())([0.1, 0.2, 0.3]), s([0.1, 0.2, 0.3]));
+    }
+
+    #[test]
+    fn tilex3_triples_extent_of_unit_cube() {
+        let a = tilex3(3, boxed([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]));
+        let (min, max) = bounds3(31, 2, 2, &a);
+        assert!(near(min, [0.0, 0.0, 0.0]));
+        assert!(near(max, [3.0, 1.0, 1.0]));
+        assert!(near(a([0.5, 0.0, 0.0]), [1.5, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn equal_radii_give_circle_and_sphere() {
+        let center = [1.0, 2.0, 3.0];
+        let (e, c) = (ellipse(center, 2.0, 2.0), circle(center, 2.0));
+        let (el, s) = (ellipsoid(center, [2.0; 3]), sphere(center, 2.0));
+        for i in 0..=4 {
+            for j in 0..=4 {
+                let (u, v) = (i as f64 / 4.0, j as f64 / 4.0);
+                assert!(near(e([u, v]), c([u, v])));
+                assert!(near(el([u, v, 0.5]), s([u, v, 0.5])));
+                assert!(near(el([u, 0.5, v]), s([u, 0.5, v])));
+            }
+        }
+    }
+
+    #[test]
+    fn boxed_corners_combine_min_and_max() {
+        let (min, max) = ([-1.0, 2.0, 0.5], [3.0, 4.0, 1.5]);
+        for (i, p) in corners3(&boxed(min, max)).iter().enumerate() {
+            for k in 0..3 {
+                assert_eq!(p[k], if (i >> k) & 1 == 0 {min[k]} else {max[k]});
+            }
+        }
+    }
+
+    #[test]
+    fn hermite_matches_bezn() {
+        let (p0, m0, p1, m1) = ([0.0, 0.0, 0.0], [3.0, 3.0, 0.0], [4.0, 1.0, 2.0], [0.0, -6.0, 3.0]);
+        let h = hermite(p0, m0, p1, m1);
+        let b = bezn(&[p0, add3(p0, scale3(m0, 1.0 / 3.0)), sub3(p1, scale3(m1, 1.0 / 3.0)), p1]);
+        for i in 0..=10 {
+            let t = i as f64 / 10.0;
+            assert!(near(h(t), b(t)));
+        }
+        assert_eq!(h(0.0), p0);
+        assert_eq!(h(1.0), p1);
+    }
+
+    #[test]
+    fn bspline_stays_in_convex_hull() {
+        let rect = [[0.0, 0.0, 0.0], [4.0, 3.0, 0.0], [4.0, 0.0, 0.0], [1.0, 2.0, 0.0], [0.0, 3.0, 0.0], [2.0, 1.0, 0.0]];
+        let tri = [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [4.0, 0.0, 0.0], [0.5, 2.0, 0.0], [0.0, 4.0, 0.0]];
+        let (a, b) = (bspline(&rect), bspline(&tri));
+        for i in 0..=100 {
+            let t = i as f64 / 100.0;
+            let (p, q) = (a(t), b(t));
+            assert!(p[0] >= 0.0 && p[0] <= 4.0 && p[1] >= 0.0 && p[1] <= 3.0 && p[2] == 0.0);
+            assert!(q[0] >= 0.0 && q[1] >= 0.0 && q[0] + q[1] <= 4.0 && q[2] == 0.0);
+        }
+    }
+
+    #[test]
+    fn constant_heightmap_is_flat() {
+        let h = heightmap([2.0, 3.0], Arc::new(|_| 0.5));
+        let p = plane([0.0, 0.0, 0.5], [2.0, 0.0, 0.0], [0.0, 3.0, 0.0]);
+        for i in 0..=4 {
+            for j in 0..=4 {
+                let t = [i as f64 / 4.0, j as f64 / 4.0];
+                assert_eq!(h(t), p(t));
+            }
+        }
+    }
+
+    #[test]
+    fn disc_and_disc_quad_cover_same_area() {
+        let normal = [1.0, 2.0, 2.0];
+        let a = surface_area(200, 200, &disc([1.0, 0.0, 0.0], 2.0, normal));
+        let b = surface_area(200, 200, &disc_quad([1.0, 0.0, 0.0], 2.0, normal));
+        assert!((a - 4.0 * PI).abs() < 0.01);
+        assert!((b - 4.0 * PI).abs() < 0.01);
+    }
+
+    #[test]
+    fn descend2_converges_on_plane() {
+        let p = plane([1.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 4.0, 1.0]);
+        let t = descend2(1e-6, 100, [1.6, 1.2, 5.0], [0.9, 0.1], &p);
+        assert!((t[0] - 0.3f64).abs() < 1e-4 && (t[1] - 0.5765f64).abs() < 1e-4);
+        let t = descend2(1e-6, 100, [5.0, 1.2, 0.3], [0.9, 0.1], &p);
+        assert!((t[0] - 1.0f64).abs() < 1e-9 && (t[1] - 0.3f64).abs() < 1e-4);
+    }
+
+    #[test]
+    fn catmull_rom_closed_square() {
+        let pts = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        let c = catmull_rom_closed(&pts);
+        for (i, p) in pts.iter().enumerate() {
+            assert!(near(c(i as f64 / 4.0), *p));
+        }
+        assert!(near(c(1.0), pts[0]));
+        let h = 1e-6;
+        let (start, end) = (sub3(c(h), c(0.0)), sub3(c(1.0), c(1.0 - h)));
+        assert!(len3(sub3(start, end)) < 1e-9);
+    }
+
+    #[test]
+    fn animate1_moves_end_point() {
+        let frames = animate1(3, Arc::new(|time| lin([0.0, 0.0, 0.0], [1.0, 2.0 * time, 0.0])));
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0](1.0), [1.0, 0.0, 0.0]);
+        assert_eq!(frames[1](1.0), [1.0, 1.0, 0.0]);
+        assert_eq!(frames[2](1.0), [1.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn centroid_and_area_of_sphere() {
+        let shell = unit_sphere_shell();
+        assert!(len3(centroid2(32, 32, &shell)) < 1e-9);
+        assert!((surface_area(64, 64, &shell) - 4.0 * PI).abs() < 0.03);
+        assert!(len3(sub3(centroid3(8, 8, 8, &boxed([0.0, 0.0, 0.0], [2.0, 4.0, 6.0])), [1.0, 2.0, 3.0])) < 1e-12);
+    }
+
+    #[test]
+    fn sphere_anim_matches_sphere_at_each_time() {
+        let anim = sphere_anim([1.0, 2.0, 3.0], Arc::new(|time: f64| 1.0 + time));
+        for &t in &[[0.0, 0.0, 1.0], [0.25, 0.5, 1.0], [0.7, 0.9, 0.5]] {
+            assert_eq!(anim(2.0, t), sphere([1.0, 2.0, 3.0], 3.0)(t));
+        }
+    }
+}
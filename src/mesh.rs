@@ -0,0 +1,976 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use vecmath::traits::*;
+
+use tables::{MC_CORNERS, MC_EDGES, MC_TRIANGLES};
+
+use {add3, cross3, dot3, floor, grid_param, ConstructError, len3, scale3, sub3, x3, y3, z3, Fn2, Fn3};
+
+/// A triangle mesh, as vertices and the vertex indices of each triangle.
+pub type Mesh<T, I = u32> = (Vec<[T; 3]>, Vec<[I; 3]>);
+
+/// Makes the winding order of triangles consistent across shared edges.
+///
+/// Each connected set of triangles is flood-filled from its first triangle,
+/// flipping neighbors that traverse a shared edge in the same direction.
+/// Afterwards, the orientation covering the largest area in the original
+/// mesh is picked as "out", such that a mostly correct mesh keeps its winding.
+///
+/// Non-orientable meshes, e.g. a Möbius strip, can not be made consistent
+/// and the result depends on the order of traversal.
+pub fn orient_consistently<T: Float>(verts: &[[T; 3]], indices: &mut [[u32; 3]]) {
+    let mut edges: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (i, tri) in indices.iter().enumerate() {
+        for k in 0..3 {
+            let (a, b) = (tri[k], tri[(k + 1) % 3]);
+            edges.entry((a.min(b), a.max(b))).or_default().push(i);
+        }
+    }
+
+    let area = |tri: &[u32; 3]| -> T {
+        let a = verts[tri[0] as usize];
+        let b = verts[tri[1] as usize];
+        let c = verts[tri[2] as usize];
+        len3(cross3(sub3(b, a), sub3(c, a)))
+    };
+
+    let mut visited = vec![false; indices.len()];
+    let mut flipped = vec![false; indices.len()];
+    let mut stack = vec![];
+    let mut component = vec![];
+    for start in 0..indices.len() {
+        if visited[start] {continue}
+        visited[start] = true;
+        stack.push(start);
+        component.clear();
+        while let Some(i) = stack.pop() {
+            component.push(i);
+            let tri = indices[i];
+            for k in 0..3 {
+                let (a, b) = (tri[k], tri[(k + 1) % 3]);
+                for &j in &edges[&(a.min(b), a.max(b))] {
+                    if visited[j] {continue}
+                    visited[j] = true;
+                    let other = indices[j];
+                    // A consistent neighbor traverses the shared edge in the opposite direction.
+                    let same_dir = (0..3).any(|n| other[n] == a && other[(n + 1) % 3] == b);
+                    if same_dir {
+                        indices[j] = [other[0], other[2], other[1]];
+                        flipped[j] = true;
+                    }
+                    stack.push(j);
+                }
+            }
+        }
+
+        let mut kept_area: T = Zero::zero();
+        let mut flipped_area: T = Zero::zero();
+        for &i in &component {
+            if flipped[i] {flipped_area += area(&indices[i])}
+            else {kept_area += area(&indices[i])}
+        }
+        if flipped_area > kept_area {
+            for &i in &component {
+                let tri = indices[i];
+                indices[i] = [tri[0], tri[2], tri[1]];
+            }
+        }
+    }
+}
+
+/// Samples a curved quad on a grid of `nu x nv` points.
+///
+/// The point at `[i / (nu - 1), j / (nv - 1)]` is stored at index `i * nv + j`.
+/// A single sample in a direction is taken at 0.
+pub fn sample_grid<T: Float>(nu: usize, nv: usize, a: &Fn2<T>) -> Vec<[T; 3]>
+    where f64: Cast<T>
+{
+    let mut verts = Vec::with_capacity(nu * nv);
+    for i in 0..nu {
+        let u = grid_param(i, nu);
+        for j in 0..nv {
+            verts.push(a([u, grid_param(j, nv)]));
+        }
+    }
+    verts
+}
+
+/// Samples a curved quad on a grid of `nu x nv` points in parallel.
+///
+/// The points are stored in the same order as `sample_grid`.
+#[cfg(feature = "rayon")]
+pub fn sample_grid_par<T: Float + Send>(nu: usize, nv: usize, a: &Fn2<T>) -> Vec<[T; 3]>
+    where f64: Cast<T>
+{
+    use rayon::prelude::*;
+
+    (0..nu * nv).into_par_iter()
+        .map(|k| a([grid_param(k / nv, nu), grid_param(k % nv, nv)]))
+        .collect()
+}
+
+/// Triangulates a curved quad sampled on a grid of `nu x nv` points.
+///
+/// Returns the vertices and the triangle indices.
+/// The number of samples is at least 2 in each direction.
+/// The vertex at `[i / (nu - 1), j / (nv - 1)]` is stored at index `i * mv + j`,
+/// where `mv` is `nv - 1` when closed in the second direction, and `nv` otherwise.
+///
+/// Each grid cell is split into two triangles,
+/// wound counter-clockwise around the direction of `normal2`.
+/// Where a row of samples collapses to a single point,
+/// e.g. at the poles of a sphere, the triangles touching it have zero area.
+///
+/// For each direction in `closed`, the last row of samples is not stored,
+/// and triangles use the first row instead.
+/// This welds the seam, e.g. of a cylinder made from a closed curve, without comparing positions.
+/// Use `close1` to make the ends of a curve match exactly before closing it.
+/// Other seams and poles are duplicated, so use `weld` before `vertex_normals`.
+///
+/// Panics if the vertex indices do not fit in `u32`.
+/// Use `try_tri_mesh` to get an error instead, or to pick another index type.
+pub fn tri_mesh<T: Float>(nu: usize, nv: usize, closed: [bool; 2], a: &Fn2<T>) -> Mesh<T>
+    where f64: Cast<T>
+{
+    try_tri_mesh(nu, nv, closed, a).expect("tri_mesh: too many vertices for u32 indices")
+}
+
+/// Triangulates a curved quad like `tri_mesh`, with a choice of index type.
+///
+/// Returns `ConstructError::TooManyVertices` if the vertex indices do not fit,
+/// before sampling the curved quad.
+pub fn try_tri_mesh<T: Float, I: MeshIndex>(
+    nu: usize,
+    nv: usize,
+    closed: [bool; 2],
+    a: &Fn2<T>
+) -> Result<Mesh<T, I>, ConstructError>
+    where f64: Cast<T>
+{
+    let (nu, nv) = (nu.max(2), nv.max(2));
+    let mu = if closed[0] {nu - 1} else {nu};
+    let mv = if closed[1] {nv - 1} else {nv};
+    check_index::<I>(mu.checked_mul(mv))?;
+    let mut verts = Vec::with_capacity(mu * mv);
+    for i in 0..mu {
+        let u = grid_param(i, nu);
+        for j in 0..mv {
+            verts.push(a([u, grid_param(j, nv)]));
+        }
+    }
+    let index = |i: usize, j: usize| I::from_usize((i % mu) * mv + j % mv);
+    let mut tris = Vec::with_capacity(2 * (nu - 1) * (nv - 1));
+    for i in 0..nu - 1 {
+        for j in 0..nv - 1 {
+            let p00 = index(i, j);
+            let p10 = index(i + 1, j);
+            let p01 = index(i, j + 1);
+            let p11 = index(i + 1, j + 1);
+            tris.push([p00, p10, p11]);
+            tris.push([p00, p11, p01]);
+        }
+    }
+    Ok((verts, tris))
+}
+
+/// Meshes a curved quad sampled on a grid of `nu x nv` points with quads.
+///
+/// Returns the vertices and the quad indices.
+/// The vertices are the same as from `tri_mesh`, and each grid cell becomes one quad,
+/// wound counter-clockwise around the direction of `normal2`.
+/// Splitting each quad along its first diagonal gives the triangles of `tri_mesh`,
+/// including the seams welded by `closed`.
+///
+/// Where a row of samples collapses to a single point, e.g. at the poles of a sphere,
+/// the quads touching it have two equal corners and are really triangles.
+/// Some tools require such quads to be removed or converted.
+///
+/// Panics if the vertex indices do not fit in `u32`.
+pub fn quad_mesh<T: Float>(
+    nu: usize,
+    nv: usize,
+    closed: [bool; 2],
+    a: &Fn2<T>
+) -> (Vec<[T; 3]>, Vec<[u32; 4]>)
+    where f64: Cast<T>
+{
+    let (verts, tris) = tri_mesh(nu, nv, closed, a);
+    // Each grid cell is split into `[p00, p10, p11]` and `[p00, p11, p01]`.
+    let quads = tris.chunks(2).map(|t| [t[0][0], t[0][1], t[0][2], t[1][2]]).collect();
+    (verts, quads)
+}
+
+/// An integer type for vertex indices of a mesh.
+///
+/// Use `u16` to save memory for small meshes, `u32` for large meshes,
+/// or `usize` to index the vertices directly.
+pub trait MeshIndex: Copy + PartialEq {
+    /// The largest vertex index that fits.
+    const MAX: usize;
+
+    /// Converts from `usize`, assuming the index is at most `MAX`.
+    fn from_usize(i: usize) -> Self;
+
+    /// Converts to `usize`.
+    fn to_usize(self) -> usize;
+}
+
+impl MeshIndex for u16 {
+    const MAX: usize = u16::MAX as usize;
+
+    fn from_usize(i: usize) -> u16 {i as u16}
+
+    fn to_usize(self) -> usize {self as usize}
+}
+
+impl MeshIndex for u32 {
+    const MAX: usize = u32::MAX as usize;
+
+    fn from_usize(i: usize) -> u32 {i as u32}
+
+    fn to_usize(self) -> usize {self as usize}
+}
+
+impl MeshIndex for usize {
+    const MAX: usize = usize::MAX;
+
+    fn from_usize(i: usize) -> usize {i}
+
+    fn to_usize(self) -> usize {self}
+}
+
+/// Checks that a number of vertices, `None` on overflow, can be indexed with an index type.
+///
+/// Every function creating vertex indices of a choice of type checks them here.
+fn check_index<I: MeshIndex>(count: Option<usize>) -> Result<(), ConstructError> {
+    match count {
+        Some(n) if n <= I::MAX.saturating_add(1) => Ok(()),
+        _ => Err(ConstructError::TooManyVertices),
+    }
+}
+
+/// Reverses the winding order of triangles in place.
+///
+/// Use this after meshing a shape transformed by a reflection,
+/// e.g. `mirror_plane` or `scl` with a negative determinant,
+/// which turns the front faces to the back.
+/// Flipping twice gives the original triangles.
+pub fn flip_winding<I: MeshIndex>(tris: &mut [[I; 3]]) {
+    for tri in tris {
+        tri.swap(1, 2);
+    }
+}
+
+/// Returns a copy of the triangles with reversed winding order, see `flip_winding`.
+pub fn flipped<I: MeshIndex>(tris: &[[I; 3]]) -> Vec<[I; 3]> {
+    tris.iter().map(|t| [t[0], t[2], t[1]]).collect()
+}
+
+/// Merges vertices closer than a tolerance and rewrites the triangle indices.
+///
+/// Vertices are looked up in a grid of cells of size `tol`,
+/// such that only neighboring cells are searched.
+/// Each vertex is merged with the first kept vertex within `tol`,
+/// so the result depends on the order of vertices when clusters are larger than `tol`.
+/// Vertices that are not finite are never merged.
+/// Triangles that collapse to a line or a point after welding are dropped.
+///
+/// For example, this merges the seam of a closed surface from `tri_mesh`
+/// and collapses the pole of a `sphere` into a single vertex.
+///
+/// Panics if the number of vertices does not fit in the index type.
+pub fn weld<T: Float, I: MeshIndex>(
+    tol: T,
+    verts: &[[T; 3]],
+    tris: &[[I; 3]]
+) -> Mesh<T, I>
+    where f64: Cast<T>
+{
+    check_index::<I>(Some(verts.len())).expect("weld: too many vertices for the index type");
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let inf = _1 / _0;
+    let cell = if tol > _0 {tol} else {_1};
+    let key = |p: [T; 3]| -> [T; 3] {[0, 1, 2].map(|k| floor(p[k] / cell))};
+    // Comparisons with NaN are false, so NaN counts as not finite.
+    let finite = |c: &[T; 3]| c.iter().all(|&x| x > -inf && x < inf);
+    // Only finite cells are compared, which are totally ordered.
+    let cmp = |a: &[T; 3], b: &[T; 3]| -> Ordering {
+        (0..3).map(|k| a[k].partial_cmp(&b[k]).unwrap_or(Ordering::Equal))
+            .find(|&o| o != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    };
+
+    // Floats can not be hashed, so the grid is a list of cells and vertices, sorted by cell.
+    let keys: Vec<[T; 3]> = verts.iter().map(|&p| key(p)).collect();
+    let mut grid: Vec<([T; 3], usize)> = keys.iter().cloned().zip(0..).filter(|e| finite(&e.0)).collect();
+    grid.sort_by(|a, b| cmp(&a.0, &b.0).then(a.1.cmp(&b.1)));
+
+    let mut welded: Vec<[T; 3]> = vec![];
+    // The index in `welded` of each vertex, which is new for kept vertices.
+    let mut remap: Vec<usize> = Vec::with_capacity(verts.len());
+    let mut kept = Vec::with_capacity(verts.len());
+    for (i, &p) in verts.iter().enumerate() {
+        let c = keys[i];
+        let mut found: Option<usize> = None;
+        if finite(&c) {
+            for &dx in &[-_1, _0, _1] {
+                for &dy in &[-_1, _0, _1] {
+                    for &dz in &[-_1, _0, _1] {
+                        let n = [c[0] + dx, c[1] + dy, c[2] + dz];
+                        let start = grid.partition_point(|e| cmp(&e.0, &n) == Ordering::Less);
+                        // Vertices in a cell are sorted, so the first match is the first kept vertex.
+                        let first = grid[start..].iter()
+                            .take_while(|e| cmp(&e.0, &n) == Ordering::Equal && e.1 < i)
+                            .map(|e| e.1)
+                            .find(|&j| kept[j] && len3(sub3(verts[j], p)) <= tol);
+                        if let Some(j) = first {found = Some(found.map_or(j, |f| f.min(j)))}
+                    }
+                }
+            }
+        }
+        kept.push(found.is_none());
+        remap.push(match found {
+            Some(j) => remap[j],
+            None => {
+                welded.push(p);
+                welded.len() - 1
+            }
+        });
+    }
+
+    let tris = tris.iter()
+        .map(|t| t.map(|i| I::from_usize(remap[i.to_usize()])))
+        .filter(|t| t[0] != t[1] && t[1] != t[2] && t[2] != t[0])
+        .collect();
+    (welded, tris)
+}
+
+/// Computes smooth vertex normals of a triangle mesh.
+///
+/// Only triangles sharing the same vertex index are averaged, not vertices at the same position.
+/// The output of `tri_mesh` duplicates vertices along seams and at poles, e.g. of `sphere`,
+/// which then get the normals of one side only and show up as creases when shading.
+/// Use `weld` on the mesh first, or close the seam with `tri_mesh` for surfaces closed along a direction.
+///
+/// Each vertex normal is the sum of the normals of the triangles using the vertex,
+/// weighted by triangle area, and then normalized.
+/// Vertices that are not used by any triangle, or only by triangles with zero area,
+/// get a zero normal.
+pub fn vertex_normals<T: Float, I: MeshIndex>(verts: &[[T; 3]], tris: &[[I; 3]]) -> Vec<[T; 3]> {
+    let _0: T = Zero::zero();
+    let mut normals = vec![[_0; 3]; verts.len()];
+    for tri in tris {
+        let a = verts[tri[0].to_usize()];
+        let b = verts[tri[1].to_usize()];
+        let c = verts[tri[2].to_usize()];
+        // The length of the cross product is twice the area of the triangle.
+        let n = cross3(sub3(b, a), sub3(c, a));
+        for &i in tri {
+            normals[i.to_usize()] = add3(normals[i.to_usize()], n);
+        }
+    }
+    for n in &mut normals {
+        let len = len3(*n);
+        *n = if len > _0 {scale3(*n, <T as One>::one() / len)} else {[_0; 3]};
+    }
+    normals
+}
+
+/// Intersects a ray with a triangle, using the Möller-Trumbore algorithm.
+///
+/// Returns the distance along the ray in units of `dir`,
+/// the barycentric coordinates of the hit for the second and third corner,
+/// and the determinant, which is positive when the ray hits the front side.
+/// Hits behind the origin are ignored.
+fn ray_triangle<T: Float>(
+    origin: [T; 3],
+    dir: [T; 3],
+    tri: [[T; 3]; 3]
+) -> Option<(T, T, T, T)> {
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let e1 = sub3(tri[1], tri[0]);
+    let e2 = sub3(tri[2], tri[0]);
+    let h = cross3(dir, e2);
+    let det = dot3(e1, h);
+    if det == _0 {return None}
+    let inv = _1 / det;
+    let s = sub3(origin, tri[0]);
+    let u = dot3(s, h) * inv;
+    if u < _0 || u > _1 {return None}
+    let q = cross3(s, e1);
+    let v = dot3(dir, q) * inv;
+    if v < _0 || u + v > _1 {return None}
+    let dist = dot3(e2, q) * inv;
+    if dist < _0 {return None}
+    Some((dist, u, v, det))
+}
+
+/// Finds the nearest intersection of a ray with a curved quad.
+///
+/// The curved quad is triangulated on a grid of `nu x nv` points, like `tri_mesh`.
+/// Returns the input of the curved quad and the position of the nearest hit,
+/// or `None` if the ray misses.
+/// The input is interpolated within the hit triangle,
+/// so both are approximate and depend on the resolution.
+///
+/// A triangle is front-facing when the ray hits the side its normal points to.
+/// Back-facing triangles are only hit when `backfaces` is `true`.
+pub fn raycast2<T: Float>(
+    origin: [T; 3],
+    dir: [T; 3],
+    nu: usize,
+    nv: usize,
+    backfaces: bool,
+    a: &Fn2<T>
+) -> Option<([T; 2], [T; 3])>
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let (nu, nv) = (nu.max(2), nv.max(2));
+    let (verts, tris) = try_tri_mesh::<T, usize>(nu, nv, [false, false], a)
+        .expect("raycast2: too many vertices");
+    let param = |k: usize| -> [T; 2] {[grid_param(k / nv, nu), grid_param(k % nv, nv)]};
+
+    let mut nearest: Option<(T, [T; 2], [T; 3])> = None;
+    for tri in &tris {
+        let hit = ray_triangle(origin, dir, [verts[tri[0]], verts[tri[1]], verts[tri[2]]]);
+        let (dist, u, v) = match hit {
+            Some((_, _, _, det)) if !backfaces && det < _0 => continue,
+            Some((dist, u, v, _)) => (dist, u, v),
+            None => continue,
+        };
+        if let Some((d, _, _)) = nearest {
+            if d <= dist {continue}
+        }
+        let (t0, t1, t2) = (param(tri[0]), param(tri[1]), param(tri[2]));
+        let w = _1 - u - v;
+        let uv = [
+            t0[0] * w + t1[0] * u + t2[0] * v,
+            t0[1] * w + t1[1] * u + t2[1] * v,
+        ];
+        nearest = Some((dist, uv, add3(origin, scale3(dir, dist))));
+    }
+    nearest.map(|(_, uv, pos)| (uv, pos))
+}
+
+/// Returns `true` if a ray from a point crosses a triangle soup an odd number of times.
+fn inside_soup<T: Float>(p: [T; 3], verts: &[[T; 3]], tris: &[[u32; 3]]) -> bool
+    where f64: Cast<T>
+{
+    // An arbitrary direction, chosen to avoid grazing axis-aligned edges.
+    let dir = [0.5364.cast(), 0.6297.cast(), 0.5619.cast()];
+    let mut inside = false;
+    for tri in tris {
+        let tri = [verts[tri[0] as usize], verts[tri[1] as usize], verts[tri[2] as usize]];
+        if ray_triangle(p, dir, tri).is_some() {inside = !inside}
+    }
+    inside
+}
+
+/// Builds an approximate signed distance function from a volume map.
+///
+/// The six faces of the input cube are triangulated like `tri_mesh`,
+/// using `nu`, `nv` and `nw` samples along the three input directions.
+/// The distance is negative inside and positive outside.
+///
+/// The vertices are welded, and triangles that coincide with another triangle,
+/// e.g. where the faces meet at the seam of `sphere`, cancel out in pairs,
+/// because they have the same inside or outside status on both sides.
+/// Triangles that collapse, e.g. at the poles of `sphere`, are ignored.
+/// A point is inside when a ray from it crosses the remaining triangles an odd number of times.
+/// The distance is to the nearest sampled point of the surface,
+/// so it is approximate and depends on the resolution.
+pub fn sdf_from3<T: Float>(
+    nu: usize,
+    nv: usize,
+    nw: usize,
+    a: &Fn3<T>
+) -> Arc<Fn([T; 3]) -> T + Sync + Send>
+    where f64: Cast<T>
+{
+    let _0: T = Zero::zero();
+    let _1: T = One::one();
+    let faces = [
+        (nv, nw, x3(_0, a.clone())),
+        (nv, nw, x3(_1, a.clone())),
+        (nu, nw, y3(_0, a.clone())),
+        (nu, nw, y3(_1, a.clone())),
+        (nu, nv, z3(_0, a.clone())),
+        (nu, nv, z3(_1, a.clone())),
+    ];
+    let mut verts = vec![];
+    let mut tris = vec![];
+    for &(n0, n1, ref face) in &faces {
+        let (face_verts, face_tris) = tri_mesh(n0, n1, [false, false], face);
+        let offset = verts.len() as u32;
+        verts.extend(face_verts);
+        tris.extend(face_tris.iter().map(|t| [t[0] + offset, t[1] + offset, t[2] + offset]));
+    }
+
+    let mut min = verts[0];
+    let mut max = verts[0];
+    for v in &verts {
+        for k in 0..3 {
+            if v[k] < min[k] {min[k] = v[k]}
+            if v[k] > max[k] {max[k] = v[k]}
+        }
+    }
+    let eps = len3(sub3(max, min)) * 0.000001.cast();
+    let (verts, tris) = weld(eps, &verts, &tris);
+
+    // Counts the triangles covering the same welded vertices, regardless of winding.
+    let key = |t: &[u32; 3]| {
+        let mut k = *t;
+        k.sort();
+        k
+    };
+    let mut count: HashMap<[u32; 3], usize> = HashMap::new();
+    for tri in &tris {
+        *count.entry(key(tri)).or_default() += 1;
+    }
+    let tris: Vec<[u32; 3]> = tris.into_iter().filter(|t| count[&key(t)] % 2 == 1).collect();
+    let mut on_surface = vec![false; verts.len()];
+    for tri in &tris {
+        for &i in tri {on_surface[i as usize] = true}
+    }
+    let surface: Vec<[T; 3]> = verts.iter().zip(&on_surface)
+        .filter(|&(_, &s)| s).map(|(&v, _)| v).collect();
+
+    return Arc::new(move |p| {
+        let mut dist: Option<T> = None;
+        for &q in &surface {
+            let d = len3(sub3(p, q));
+            if dist.map(|m| d < m).unwrap_or(true) {dist = Some(d)}
+        }
+        let dist = dist.unwrap_or(_0);
+        if inside_soup(p, &verts, &tris) {-dist} else {dist}
+    })
+}
+
+/// Extracts a triangle mesh of an iso surface using marching cubes.
+///
+/// The box from `min` to `max` is split into `res` cells along each axis,
+/// at least one, and `f` is sampled at the cell corners.
+/// Vertices are linearly interpolated along cell edges where `f` crosses `iso`.
+/// Each edge gets a single vertex shared by all cells touching it,
+/// so the mesh is watertight where the surface does not leave the box.
+///
+/// Triangles are wound counter-clockwise around the direction in which `f` increases,
+/// which is outward for a signed distance function with `iso` zero.
+///
+/// Returns `ConstructError::TooManyVertices` if the vertex indices do not fit in the index type.
+pub fn marching_cubes<T: Float, I: MeshIndex>(
+    min: [T; 3],
+    max: [T; 3],
+    res: [usize; 3],
+    iso: T,
+    f: &Fn([T; 3]) -> T
+) -> Result<Mesh<T, I>, ConstructError>
+    where f64: Cast<T>
+{
+    let _05: T = 0.5.cast();
+    let res = [res[0].max(1), res[1].max(1), res[2].max(1)];
+    let (nx, ny) = (res[0] + 1, res[1] + 1);
+    let index = |c: [usize; 3]| (c[2] * ny + c[1]) * nx + c[0];
+    let pos = |c: [usize; 3]| -> [T; 3] {
+        let mut p = min;
+        for k in 0..3 {
+            let s: T = (c[k] as f64 / res[k] as f64).cast();
+            p[k] = min[k] + (max[k] - min[k]) * s;
+        }
+        p
+    };
+
+    let mut values = Vec::with_capacity(nx * ny * (res[2] + 1));
+    for z in 0..res[2] + 1 {
+        for y in 0..ny {
+            for x in 0..nx {
+                values.push(f(pos([x, y, z])));
+            }
+        }
+    }
+
+    let mut verts = vec![];
+    let mut tris = vec![];
+    // Maps an edge, as the index of its lower corner and its axis, to a vertex.
+    let mut cache: HashMap<(usize, usize), I> = HashMap::new();
+    for z in 0..res[2] {
+        for y in 0..res[1] {
+            for x in 0..res[0] {
+                let corner = |i: usize| {
+                    let o = MC_CORNERS[i];
+                    [x + o[0], y + o[1], z + o[2]]
+                };
+                let mut case = 0;
+                for i in 0..8 {
+                    if values[index(corner(i))] < iso {case |= 1 << i}
+                }
+                let edges = &MC_TRIANGLES[case];
+                let mut vertex = |e: usize| -> Result<I, ConstructError> {
+                    let (c0, c1) = (corner(MC_EDGES[e][0]), corner(MC_EDGES[e][1]));
+                    let (c0, c1) = if index(c0) < index(c1) {(c0, c1)} else {(c1, c0)};
+                    let axis = (0..3).find(|&k| c0[k] != c1[k]).unwrap();
+                    let key = (index(c0), axis);
+                    if let Some(&i) = cache.get(&key) {return Ok(i)}
+                    let (v0, v1) = (values[index(c0)], values[index(c1)]);
+                    let s = if v0 == v1 {_05} else {(iso - v0) / (v1 - v0)};
+                    let (p0, p1) = (pos(c0), pos(c1));
+                    check_index::<I>(Some(verts.len() + 1))?;
+                    let i = I::from_usize(verts.len());
+                    verts.push(add3(p0, scale3(sub3(p1, p0), s)));
+                    cache.insert(key, i);
+                    Ok(i)
+                };
+                let mut k = 0;
+                while edges[k] >= 0 {
+                    let a = vertex(edges[k] as usize)?;
+                    let b = vertex(edges[k + 1] as usize)?;
+                    let c = vertex(edges[k + 2] as usize)?;
+                    tris.push([a, c, b]);
+                    k += 3;
+                }
+            }
+        }
+    }
+    Ok((verts, tris))
+}
+
+/// Triangulates a curved quad at several levels of detail.
+///
+/// Returns `levels` meshes like `tri_mesh`, starting at `base` samples in each direction.
+/// Each following level halves the number of grid cells in each direction,
+/// but never goes below a single cell, i.e. 2 samples.
+/// When the number of cells is even, the vertices of a level are a subset of the previous level.
+///
+/// Returns `ConstructError::TooManyVertices` if the vertex indices do not fit in the index type.
+pub fn lods2<T: Float, I: MeshIndex>(
+    base: (usize, usize),
+    levels: usize,
+    a: &Fn2<T>
+) -> Result<Vec<Mesh<T, I>>, ConstructError>
+    where f64: Cast<T>
+{
+    let (mut nu, mut nv) = (base.0.max(2), base.1.max(2));
+    let mut res = Vec::with_capacity(levels);
+    for _ in 0..levels {
+        res.push(try_tri_mesh(nu, nv, [false, false], a)?);
+        nu = ((nu - 1) / 2).max(1) + 1;
+        nv = ((nv - 1) / 2).max(1) + 1;
+    }
+    Ok(res)
+}
+
+/// Gets the grid lines of a curved quad sampled on a grid of `nu x nv` points.
+///
+/// Returns line segments between neighboring samples, using at least 2 samples in each direction.
+/// Each edge of the grid is returned once, first the `(nu - 1) * nv` segments along the first
+/// input argument, then the `nu * (nv - 1)` segments along the second input argument.
+/// Where a row of samples collapses to a single point, e.g. at the poles of a sphere,
+/// the segments have zero length.
+pub fn wireframe2<T: Float>(nu: usize, nv: usize, a: &Fn2<T>) -> Vec<([T; 3], [T; 3])>
+    where f64: Cast<T>
+{
+    let (nu, nv) = (nu.max(2), nv.max(2));
+    let verts = sample_grid(nu, nv, a);
+    let mut lines = Vec::with_capacity((nu - 1) * nv + nu * (nv - 1));
+    for i in 0..nu - 1 {
+        for j in 0..nv {
+            lines.push((verts[i * nv + j], verts[(i + 1) * nv + j]));
+        }
+    }
+    for i in 0..nu {
+        for j in 0..nv - 1 {
+            lines.push((verts[i * nv + j], verts[i * nv + j + 1]));
+        }
+    }
+    lines
+}
+
+/// Intersects a triangle with a plane `dot(n, p) = d`, returning the segment of the triangle
+/// in the plane, or `None` if the triangle does not cross the plane.
+fn clip_triangle<T: Float>(tri: [[T; 3]; 3], n: [T; 3], d: T) -> Option<([T; 3], [T; 3])> {
+    let _0: T = Zero::zero();
+    let s = [dot3(n, tri[0]) - d, dot3(n, tri[1]) - d, dot3(n, tri[2]) - d];
+    let mut points = Vec::with_capacity(3);
+    for i in 0..3 {
+        let j = (i + 1) % 3;
+        if s[i] == _0 {points.push(tri[i])}
+        if (s[i] < _0 && s[j] > _0) || (s[i] > _0 && s[j] < _0) {
+            let f = s[i] / (s[i] - s[j]);
+            points.push(add3(tri[i], scale3(sub3(tri[j], tri[i]), f)));
+        }
+    }
+    if points.len() == 2 {Some((points[0], points[1]))} else {None}
+}
+
+/// Computes the segment where two triangles intersect, if any.
+fn intersect_triangles<T: Float>(a: [[T; 3]; 3], b: [[T; 3]; 3]) -> Option<([T; 3], [T; 3])> {
+    let _0: T = Zero::zero();
+    let na = cross3(sub3(a[1], a[0]), sub3(a[2], a[0]));
+    let nb = cross3(sub3(b[1], b[0]), sub3(b[2], b[0]));
+    let dir = cross3(na, nb);
+    if dot3(dir, dir) == _0 {return None}
+    let (a0, a1) = clip_triangle(a, nb, dot3(nb, b[0]))?;
+    let (b0, b1) = clip_triangle(b, na, dot3(na, a[0]))?;
+    // Both segments lie on the line where the planes meet, so they overlap along it.
+    let (sa0, sa1) = (dot3(dir, a0), dot3(dir, a1));
+    let (sb0, sb1) = (dot3(dir, b0), dot3(dir, b1));
+    let (a0, a1, sa0, sa1) = if sa0 <= sa1 {(a0, a1, sa0, sa1)} else {(a1, a0, sa1, sa0)};
+    let (b0, b1) = if sb0 <= sb1 {(b0, b1)} else {(b1, b0)};
+    let (sb0, sb1) = (sb0.min(sb1), sb0.max(sb1));
+    let start = if sb0 > sa0 {b0} else {a0};
+    let end = if sb1 < sa1 {b1} else {a1};
+    if sa0.max(sb0) < sa1.min(sb1) {Some((start, end))} else {None}
+}
+
+/// Computes the curves where two curved quads intersect.
+///
+/// Both curved quads are triangulated like `tri_mesh` on a grid of `nu x nv` points,
+/// and the segments where their triangles intersect are joined into polylines.
+/// Returns one list of points per polyline, where a closed loop ends at its first point.
+///
+/// The result lies on both triangle meshes, so it is approximate and depends on the resolution.
+/// Every pair of triangles is tested, so the cost grows with the square of the number of triangles.
+pub fn intersect_surfaces<T: Float>(nu: usize, nv: usize, a: &Fn2<T>, b: &Fn2<T>) -> Vec<Vec<[T; 3]>>
+    where f64: Cast<T>
+{
+    let (verts_a, tris_a) = tri_mesh(nu, nv, [false, false], a);
+    let (verts_b, tris_b) = tri_mesh(nu, nv, [false, false], b);
+    let tri = |verts: &[[T; 3]], t: &[u32; 3]| {
+        [verts[t[0] as usize], verts[t[1] as usize], verts[t[2] as usize]]
+    };
+    let bounds = |t: &[[T; 3]; 3]| {
+        let min = [0, 1, 2].map(|k| t[0][k].min(t[1][k]).min(t[2][k]));
+        let max = [0, 1, 2].map(|k| t[0][k].max(t[1][k]).max(t[2][k]));
+        (min, max)
+    };
+    let boxes_b: Vec<_> = tris_b.iter().map(|t| bounds(&tri(&verts_b, t))).collect();
+
+    let mut segments = vec![];
+    let mut scale: T = Zero::zero();
+    for ta in &tris_a {
+        let ta = tri(&verts_a, ta);
+        let (min_a, max_a) = bounds(&ta);
+        scale = scale.max(len3(sub3(max_a, min_a)));
+        for (tb, &(min_b, max_b)) in tris_b.iter().zip(&boxes_b) {
+            if (0..3).any(|k| max_a[k] < min_b[k] || max_b[k] < min_a[k]) {continue}
+            if let Some(seg) = intersect_triangles(ta, tri(&verts_b, tb)) {
+                segments.push(seg);
+            }
+        }
+    }
+
+    // Joins segments whose ends are closer than a tiny fraction of the triangle size.
+    let tol = scale * 0.000001.cast();
+    let near = |p: [T; 3], q: [T; 3]| len3(sub3(p, q)) <= tol;
+    // Segments along shared edges are found once for each triangle sharing the edge.
+    let mut unique: Vec<([T; 3], [T; 3])> = Vec::with_capacity(segments.len());
+    for &(p, q) in &segments {
+        if near(p, q) {continue}
+        let dup = unique.iter().any(|&(s, e)| (near(p, s) && near(q, e)) || (near(p, e) && near(q, s)));
+        if !dup {unique.push((p, q))}
+    }
+    let mut segments = unique;
+    let mut lines = vec![];
+    while let Some((p, q)) = segments.pop() {
+        let mut line = vec![p, q];
+        let mut extended = true;
+        while extended {
+            extended = false;
+            for i in 0..segments.len() {
+                let (s, e) = segments[i];
+                let (first, last) = (line[0], line[line.len() - 1]);
+                if near(s, last) {line.push(e)}
+                else if near(e, last) {line.push(s)}
+                else if near(e, first) {line.insert(0, s)}
+                else if near(s, first) {line.insert(0, e)}
+                else {continue}
+                segments.swap_remove(i);
+                extended = true;
+                break;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tests::flat;
+
+    #[test]
+    fn mesh_index_overflow_is_an_error() {
+        // 257 x 256 = 65792 vertices do not fit in `u16`.
+        let too_many = Some(ConstructError::TooManyVertices);
+        assert_eq!(try_tri_mesh::<f64, u16>(257, 256, [false, false], &flat()).err(), too_many);
+        assert_eq!(lods2::<f64, u16>((257, 256), 3, &flat()).err(), too_many);
+        // Closing a direction drops a row, so the same grid fits.
+        assert!(try_tri_mesh::<f64, u16>(257, 256, [true, false], &flat()).is_ok());
+        // A plane crossing each of the 257 x 257 vertical cell edges once.
+        let plane = |p: [f64; 3]| p[2];
+        let (min, max) = ([0.0, 0.0, -1.0], [1.0, 1.0, 1.0]);
+        assert_eq!(marching_cubes::<f64, u16>(min, max, [256, 256, 1], 0.0, &plane).err(), too_many);
+        assert!(marching_cubes::<f64, u16>(min, max, [255, 255, 1], 0.0, &plane).is_ok());
+        let (verts, tris) = try_tri_mesh::<f64, u16>(256, 256, [false, false], &flat()).unwrap();
+        assert_eq!(verts.len(), 65536);
+        assert_eq!(tris.iter().flat_map(|t| t.iter()).max(), Some(&u16::MAX));
+    }
+
+    #[test]
+    fn sdf_of_sphere_has_inside_and_outside_sign() {
+        let sdf = sdf_from3(16, 16, 4, &::sphere([1.0f64, 2.0, 3.0], 2.0));
+        let d = sdf([1.0, 2.0, 3.0]);
+        assert!(d < 0.0 && (d + 2.0).abs() < 0.1);
+        assert!(sdf([1.5, 1.5, 3.5]) < 0.0);
+        assert!(sdf([1.0, 2.0, 6.0]) > 0.0);
+        assert!(sdf([4.0, 2.5, 3.0]) > 0.0);
+        assert!(sdf([-5.0, -5.0, -5.0]) > 0.0);
+        let d = sdf([1.0, 5.0, 3.0]);
+        assert!(d > 0.0 && (d - 1.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn tri_mesh_counts() {
+        for &(nu, nv) in &[(2, 2), (5, 3), (7, 11)] {
+            let (verts, tris) = tri_mesh(nu, nv, [false, false], &flat());
+            assert_eq!(verts.len(), nu * nv);
+            assert_eq!(tris.len(), 2 * (nu - 1) * (nv - 1));
+            assert!(tris.iter().all(|t| t.iter().all(|&i| (i as usize) < verts.len())));
+        }
+    }
+
+    #[test]
+    fn raycast_hits_flat_surface() {
+        let a = ::ext1(::lin([0.0, 0.0, 0.0], [2.0, 0.0, 0.0]), ::lin([0.0, 0.0, 0.0], [0.0, 2.0, 0.0]));
+        let (uv, p) = raycast2([0.5, 1.5, 3.0], [0.0, 0.0, -1.0], 8, 8, false, &a).unwrap();
+        assert!((uv[0] - 0.25f64).abs() < 1e-9 && (uv[1] - 0.75f64).abs() < 1e-9);
+        assert!(len3(sub3(p, [0.5, 1.5, 0.0])) < 1e-9);
+        assert!(raycast2([0.5, 1.5, -3.0], [0.0, 0.0, 1.0], 8, 8, false, &a).is_none());
+        assert!(raycast2([0.5, 1.5, -3.0], [0.0, 0.0, 1.0], 8, 8, true, &a).is_some());
+        assert!(raycast2([3.0, 1.5, 3.0], [0.0, 0.0, -1.0], 8, 8, true, &a).is_none());
+    }
+
+    #[test]
+    fn marching_cubes_of_sphere_sdf() {
+        let sdf = |p: [f64; 3]| len3(p) - 1.0;
+        let (verts, tris): Mesh<f64> = marching_cubes([-1.5; 3], [1.5; 3], [16; 3], 0.0, &sdf).unwrap();
+        assert!(!tris.is_empty());
+        for v in &verts {
+            assert!((len3(*v) - 1.0).abs() < 0.02);
+        }
+        for t in &tris {
+            assert!(t.iter().all(|&i| (i as usize) < verts.len()));
+        }
+    }
+
+    #[test]
+    fn lods2_vertex_counts_quarter() {
+        let levels: Vec<Mesh<f64>> = lods2((33, 33), 4, &flat()).unwrap();
+        let counts: Vec<usize> = levels.iter().map(|l| l.0.len()).collect();
+        assert_eq!(counts, vec![33 * 33, 17 * 17, 9 * 9, 5 * 5]);
+        for w in counts.windows(2) {
+            let ratio = w[0] as f64 / w[1] as f64;
+            assert!(ratio > 3.0 && ratio < 4.5);
+        }
+    }
+
+    #[test]
+    fn wireframe2_segment_counts() {
+        let a = ::plane([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        let (nu, nv) = (4, 3);
+        let lines = wireframe2(nu, nv, &a);
+        assert_eq!(lines.len(), (nu - 1) * nv + nu * (nv - 1));
+        let (along_u, along_v) = lines.split_at((nu - 1) * nv);
+        assert!(along_u.iter().all(|&(p, q)| p[1] == q[1] && (q[0] - p[0] - 1.0 / 3.0f64).abs() < 1e-12));
+        assert!(along_v.iter().all(|&(p, q)| p[0] == q[0] && (q[1] - p[1] - 0.5f64).abs() < 1e-12));
+    }
+
+    #[test]
+    fn quad_mesh_counts() {
+        let (verts, quads) = quad_mesh(5, 7, [false, false], &flat());
+        assert_eq!(verts.len(), 5 * 7);
+        assert_eq!(quads.len(), 4 * 6);
+        assert!(quads.iter().all(|q| q.iter().all(|&i| (i as usize) < verts.len())));
+    }
+
+    #[test]
+    fn intersect_perpendicular_planes() {
+        let a = ::plane([-1.0, -1.0, 0.0], [2.0, 0.0, 0.0], [0.0, 2.0, 0.0]);
+        let b = ::plane([0.3, -1.0, -1.0], [0.0, 2.0, 0.0], [0.0, 0.0, 2.0]);
+        let curves = intersect_surfaces(5, 5, &a, &b);
+        assert!(!curves.is_empty());
+        let (mut lo, mut hi) = (0.0f64, 0.0f64);
+        for p in curves.iter().flat_map(|c| c.iter()) {
+            assert!((p[0] - 0.3f64).abs() < 1e-9 && p[2].abs() < 1e-9);
+            lo = lo.min(p[1]);
+            hi = hi.max(p[1]);
+        }
+        assert!(lo < -1.0 + 1e-9 && hi > 1.0 - 1e-9);
+    }
+
+    #[test]
+    fn weld_keeps_vertices_that_are_not_finite() {
+        let nan = f64::NAN;
+        let verts = [[0.0, 0.0, 0.0], [nan, 0.0, 0.0], [1e-12, 0.0, 0.0], [nan, 0.0, 0.0], [-0.5, 0.0, 0.0]];
+        let (welded, tris) = weld(1e-9, &verts, &[[0u32, 1, 4], [2, 3, 4]]);
+        assert_eq!(welded.len(), 4);
+        assert_eq!(tris, vec![[0, 1, 3], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn flipping_twice_restores_winding() {
+        let (_, tris) = tri_mesh(4, 5, [false, false], &flat());
+        let mut t = tris.clone();
+        flip_winding(&mut t);
+        assert_ne!(t, tris);
+        assert_eq!(flipped(&t), tris);
+        flip_winding(&mut t);
+        assert_eq!(t, tris);
+    }
+
+    #[test]
+    fn vertex_normals_of_welded_sphere_point_outward() {
+        let (verts, tris) = tri_mesh(24, 24, [false, false], &::tests::unit_sphere_shell());
+        let (verts, tris) = weld(1e-9, &verts, &tris);
+        let normals = vertex_normals(&verts, &tris);
+        for (p, n) in verts.iter().zip(&normals) {
+            assert!(dot3(*p, *n) > 0.99);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn sample_grid_par_matches_serial() {
+        let shell = ::tests::unit_sphere_shell();
+        for &(nu, nv) in &[(1, 1), (7, 13), (32, 3)] {
+            assert_eq!(sample_grid_par(nu, nv, &shell), sample_grid(nu, nv, &shell));
+        }
+    }
+
+    #[test]
+    fn tri_mesh_closes_sphere_seam() {
+        let shell = ::tests::unit_sphere_shell();
+        let (verts, tris) = tri_mesh(9, 9, [true, false], &shell);
+        assert_eq!((verts.len(), tris.len()), (72, 128));
+        assert!(tris.iter().all(|t| t.iter().all(|&i| (i as usize) < verts.len())));
+        // The last cell uses the vertex at `[0, 7 / 8]` instead of `[1, 7 / 8]`.
+        assert_eq!(tris[tris.len() - 2][1], 7);
+    }
+
+    #[test]
+    fn weld_merges_sphere_seam_and_poles() {
+        let (verts, tris) = tri_mesh(9, 9, [false, false], &::tests::unit_sphere_shell());
+        assert_eq!((verts.len(), tris.len()), (81, 128));
+        // The seam shares 9 vertices, and each pole collapses 9 vertices into one.
+        let (verts, tris) = weld(1e-9, &verts, &tris);
+        assert_eq!((verts.len(), tris.len()), (58, 112));
+        assert!(tris.iter().all(|t| t.iter().all(|&i| (i as usize) < verts.len())));
+    }
+}
@@ -0,0 +1,83 @@
+//! Wrappers for chaining transforms with operators.
+//!
+//! Nested calls like `off(p, rot(axis, angle, a))` read inside out.
+//! With the wrappers, the same transform reads from left to right:
+//!
+//! ```ignore
+//! let b = Curve(a).then(|a| rot(axis, angle, a)) + p;
+//! ```
+//!
+//! Adding an offset is the same as `off`,
+//! and multiplying with a row major matrix is the same as `affine`.
+//! Use `into_inner` to pass the result to other functions.
+
+use std::ops::{Add, Mul};
+
+use vecmath::traits::*;
+
+use {affine, off, Fn1, Fn2, Fn3};
+
+macro_rules! shape {
+    ($name:ident, $fun:ident, $kind:expr) => {
+        #[doc = "Wraps a"]
+        #[doc = $kind]
+        #[doc = "for chaining transforms with operators."]
+        #[derive(Clone)]
+        pub struct $name<T>(pub $fun<T>);
+
+        impl<T: Float> $name<T> {
+            /// Gets the wrapped function.
+            pub fn into_inner(self) -> $fun<T> {
+                self.0
+            }
+
+            /// Applies a transform to the wrapped function.
+            pub fn then<F>(self, f: F) -> $name<T>
+                where F: FnOnce($fun<T>) -> $fun<T>
+            {
+                $name(f(self.0))
+            }
+        }
+
+        impl<T: Float> Add<[T; 3]> for $name<T> {
+            type Output = $name<T>;
+
+            fn add(self, pos: [T; 3]) -> $name<T> {
+                $name(off(pos, self.0))
+            }
+        }
+
+        impl<T: Float> Mul<[[T; 4]; 4]> for $name<T> {
+            type Output = $name<T>;
+
+            fn mul(self, m: [[T; 4]; 4]) -> $name<T> {
+                $name(affine(m, self.0))
+            }
+        }
+    }
+}
+
+shape!{Curve, Fn1, "curve"}
+shape!{Surface, Fn2, "curved quad"}
+shape!{Solid, Fn3, "volume map"}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adding_offset_is_off() {
+        let a = ::lin([0.0, 1.0, 2.0], [3.0, 1.0, 0.0]);
+        let p = [0.5, -1.0, 2.0];
+        let b = (Curve(a.clone()) + p).into_inner();
+        let c = off(p, a.clone());
+        for i in 0..=4 {
+            let t = i as f64 / 4.0;
+            assert_eq!(b(t), c(t));
+        }
+        let m = [[2.0, 0.0, 0.0, 1.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+        let d = Curve(a.clone()).then(|a| ::mx(0.0, a)).then(|a| affine(m, a)).into_inner();
+        let e = (Curve(::mx(0.0, a)) * m).into_inner();
+        assert_eq!(d(0.5), e(0.5));
+    }
+}
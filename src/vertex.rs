@@ -0,0 +1,69 @@
+//! Interleaved vertex buffers for rendering.
+//!
+//! Enabled with the `piston` feature.
+
+use vecmath::traits::*;
+
+use {grid_param, try_tri_mesh, vertex_normals, ConstructError, Fn2, MeshIndex};
+
+/// A vertex with position, normal and texture coordinates.
+///
+/// The layout is the same as a C struct of 8 floats, suitable for vertex buffers.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    /// The position.
+    pub pos: [f32; 3],
+    /// The unit normal, or zero where the surface is degenerate.
+    pub normal: [f32; 3],
+    /// The input of the curved quad, as texture coordinates.
+    pub uv: [f32; 2],
+}
+
+/// Triangulates a curved quad into vertices and a flat index buffer.
+///
+/// The vertices and triangles are the same as from `tri_mesh`,
+/// with normals from `vertex_normals` and texture coordinates from the input.
+/// Since vertices on seams are not shared, normals are not smoothed across seams.
+///
+/// Returns `ConstructError::TooManyVertices` if the vertex indices do not fit in the index type.
+pub fn to_pos_normal_uv<T: Float + Cast<f32>, I: MeshIndex>(
+    nu: usize,
+    nv: usize,
+    a: &Fn2<T>
+) -> Result<(Vec<Vertex>, Vec<I>), ConstructError>
+    where f64: Cast<T>
+{
+    let (nu, nv) = (nu.max(2), nv.max(2));
+    let (verts, tris) = try_tri_mesh::<T, I>(nu, nv, [false, false], a)?;
+    let normals = vertex_normals(&verts, &tris);
+    let cast = |v: [T; 3]| -> [f32; 3] {[v[0].cast(), v[1].cast(), v[2].cast()]};
+    let vertices = verts.iter().zip(&normals).enumerate().map(|(k, (&pos, &normal))| {
+        Vertex {
+            pos: cast(pos),
+            normal: cast(normal),
+            uv: [grid_param::<f32>(k / nv, nu), grid_param::<f32>(k % nv, nv)],
+        }
+    }).collect();
+    let indices = tris.iter().flat_map(|t| t.iter().cloned()).collect();
+    Ok((vertices, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vertex_layout_and_counts() {
+        use std::mem::size_of;
+
+        assert_eq!(size_of::<Vertex>(), 8 * size_of::<f32>());
+        let shell = ::tests::unit_sphere_shell();
+        let (vertices, indices) = to_pos_normal_uv::<f64, u16>(6, 5, &shell).unwrap();
+        let (verts, tris) = ::tri_mesh(6, 5, [false, false], &shell);
+        assert_eq!(vertices.len(), verts.len());
+        assert_eq!(indices.len(), 3 * tris.len());
+        assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+        assert_eq!(vertices[6 * 5 - 1].uv, [1.0, 1.0]);
+    }
+}